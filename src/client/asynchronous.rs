@@ -0,0 +1,453 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    client::{positive, ClientError},
+    session::ClientSession,
+    types::{
+        AnyResponse, AuthData, AuthResponse, Capability, Command, DropListing, Greeting, Mechanism,
+        MessageNumber, ScanListing, SingleLine, UniqueIdListing,
+    },
+};
+
+/// Executes `$command` and returns the payload of the positive `$variant` response.
+macro_rules! execute {
+    ($self:ident, $command:expr, $variant:ident) => {
+        match $self.execute($command).await? {
+            AnyResponse::$variant(response) => positive(response),
+            _ => Err(ClientError::Desync),
+        }
+    };
+}
+
+/// Async POP3 client over any `AsyncRead + AsyncWrite` transport.
+///
+/// Negative responses are returned as [ClientError::Negative].
+///
+/// Note: When a call is cancelled (i.e., its future is dropped) while waiting for a response, the
+/// response is never read and all further calls return [ClientError::Desync].
+///
+/// ```rust,no_run
+/// # use tokio::io::{AsyncRead, AsyncWrite};
+/// # async fn example<S>(stream: S) -> Result<(), pop3_codec::client::ClientError>
+/// # where
+/// #     S: AsyncRead + AsyncWrite + Unpin,
+/// # {
+/// use pop3_codec::{client::AsyncClient, types::MessageNumber};
+///
+/// // E.g., a `tokio::net::TcpStream`.
+/// let mut client = AsyncClient::connect(stream).await?;
+///
+/// client.login("alice", "secret").await?;
+/// let listing = client.stat().await?;
+///
+/// for msg in (1..=listing.message_count).filter_map(MessageNumber::new) {
+///     let message = client.retr(msg).await?;
+///     println!("{}", String::from_utf8_lossy(&message));
+/// }
+///
+/// client.quit().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncClient<S> {
+    stream: S,
+    session: ClientSession,
+    greeting: Greeting<'static>,
+}
+
+impl<S> AsyncClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Reads the greeting from `stream`.
+    pub async fn connect(stream: S) -> Result<Self, ClientError> {
+        let mut client = Self {
+            stream,
+            session: ClientSession::new(),
+            greeting: Greeting {
+                code: None,
+                comment: "".into(),
+                timestamp: None,
+            },
+        };
+
+        match client.read_response().await? {
+            AnyResponse::Greeting(greeting) => client.greeting = greeting,
+            _ => return Err(ClientError::Desync),
+        }
+
+        Ok(client)
+    }
+
+    /// Returns the greeting of the server.
+    pub fn greeting(&self) -> &Greeting<'static> {
+        &self.greeting
+    }
+
+    /// Returns the underlying session.
+    pub fn session(&self) -> &ClientSession {
+        &self.session
+    }
+
+    /// Returns the underlying transport.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    pub async fn user(&mut self, name: &str) -> Result<SingleLine<'static>, ClientError> {
+        execute!(self, Command::User(name.into()), User)
+    }
+
+    pub async fn pass(&mut self, password: &str) -> Result<SingleLine<'static>, ClientError> {
+        execute!(self, Command::Pass(password.into()), Pass)
+    }
+
+    /// Authenticates using USER and PASS.
+    pub async fn login(
+        &mut self,
+        name: &str,
+        password: &str,
+    ) -> Result<SingleLine<'static>, ClientError> {
+        self.user(name).await?;
+        self.pass(password).await
+    }
+
+    pub async fn apop(
+        &mut self,
+        name: &str,
+        digest: &str,
+    ) -> Result<SingleLine<'static>, ClientError> {
+        execute!(
+            self,
+            Command::Apop {
+                name: name.into(),
+                digest: digest.into(),
+            },
+            Apop
+        )
+    }
+
+    /// Authenticates using AUTH (RFC 5034).
+    ///
+    /// Every (base64 encoded) challenge of the server is passed to `respond`.
+    pub async fn auth<'r, F>(
+        &mut self,
+        mechanism: Mechanism,
+        initial_response: Option<String>,
+        mut respond: F,
+    ) -> Result<SingleLine<'static>, ClientError>
+    where
+        F: FnMut(&str) -> AuthData<'r>,
+    {
+        self.check_sync()?;
+
+        let mut bytes = self.session.send(Command::Auth {
+            mechanism,
+            initial_response: initial_response.map(Into::into),
+        })?;
+
+        loop {
+            self.write(&bytes).await?;
+
+            match self.read_response().await? {
+                AnyResponse::Auth(AuthResponse::Continue(challenge)) => {
+                    bytes = self.session.send_auth_data(respond(&challenge))?;
+                }
+                AnyResponse::Auth(AuthResponse::Done(response)) => return positive(response),
+                _ => return Err(ClientError::Desync),
+            }
+        }
+    }
+
+    pub async fn capa(&mut self) -> Result<Vec<Capability<'static>>, ClientError> {
+        execute!(self, Command::Capa, Capa).map(|capabilities| capabilities.body)
+    }
+
+    pub async fn stat(&mut self) -> Result<DropListing<'static>, ClientError> {
+        execute!(self, Command::Stat, Stat)
+    }
+
+    pub async fn list_all(&mut self) -> Result<Vec<ScanListing<'static>>, ClientError> {
+        execute!(self, Command::ListAll, ListAll).map(|listing| listing.body)
+    }
+
+    pub async fn list(&mut self, msg: MessageNumber) -> Result<ScanListing<'static>, ClientError> {
+        execute!(self, Command::List { msg }, List)
+    }
+
+    pub async fn uidl_all(&mut self) -> Result<Vec<UniqueIdListing<'static>>, ClientError> {
+        execute!(self, Command::UidlAll, UidlAll).map(|listing| listing.body)
+    }
+
+    pub async fn uidl(
+        &mut self,
+        msg: MessageNumber,
+    ) -> Result<UniqueIdListing<'static>, ClientError> {
+        execute!(self, Command::Uidl { msg }, Uidl)
+    }
+
+    /// Retrieves the (unstuffed) message.
+    pub async fn retr(&mut self, msg: MessageNumber) -> Result<Vec<u8>, ClientError> {
+        execute!(self, Command::Retr { msg }, Retr).map(|message| message.body_bytes())
+    }
+
+    /// Retrieves the header and the first `n` lines of the body of the message.
+    pub async fn top(&mut self, msg: MessageNumber, n: u32) -> Result<Vec<u8>, ClientError> {
+        execute!(self, Command::Top { msg, n }, Top).map(|message| message.body_bytes())
+    }
+
+    pub async fn dele(&mut self, msg: MessageNumber) -> Result<SingleLine<'static>, ClientError> {
+        execute!(self, Command::Dele { msg }, Dele)
+    }
+
+    pub async fn noop(&mut self) -> Result<SingleLine<'static>, ClientError> {
+        execute!(self, Command::Noop, Noop)
+    }
+
+    pub async fn rset(&mut self) -> Result<SingleLine<'static>, ClientError> {
+        execute!(self, Command::Rset, Rset)
+    }
+
+    /// Ends the session, i.e., the server removes messages marked as deleted.
+    pub async fn quit(mut self) -> Result<SingleLine<'static>, ClientError> {
+        let response = execute!(self, Command::Quit, Quit);
+        self.stream.shutdown().await?;
+
+        response
+    }
+
+    async fn execute(&mut self, command: Command<'_>) -> Result<AnyResponse<'static>, ClientError> {
+        self.check_sync()?;
+
+        let bytes = self.session.send(command)?;
+        self.write(&bytes).await?;
+        self.read_response().await
+    }
+
+    /// Fails when a previous call was cancelled, i.e., its response was not read.
+    fn check_sync(&self) -> Result<(), ClientError> {
+        match self.session.pending() {
+            Some(_) => Err(ClientError::Desync),
+            None => Ok(()),
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), ClientError> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await?;
+
+        Ok(())
+    }
+
+    async fn read_response(&mut self) -> Result<AnyResponse<'static>, ClientError> {
+        let mut buffer = [0; 4096];
+
+        loop {
+            if let Some(response) = self.session.next_event()? {
+                return Ok(response);
+            }
+
+            match self.stream.read(&mut buffer).await? {
+                0 => return Err(ClientError::ConnectionClosed),
+                n => self.session.receive(&buffer[..n]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use tokio::io::{duplex, DuplexStream};
+
+    use super::*;
+    use crate::{
+        session::{ServerEvent, ServerSession},
+        types::{MultiLine, Response},
+    };
+
+    fn ok(comment: &str) -> Response<SingleLine<'_>, SingleLine<'_>> {
+        Response::Ok(SingleLine {
+            code: None,
+            comment: comment.into(),
+        })
+    }
+
+    fn multi_line<T>(body: Vec<T>) -> Response<MultiLine<'static, T>, SingleLine<'static>>
+    where
+        T: std::fmt::Debug + Clone + PartialEq + Eq,
+    {
+        Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "".into(),
+            },
+            body,
+        })
+    }
+
+    /// Serves a maildrop with two messages over `stream`.
+    async fn mock_server(mut stream: DuplexStream) {
+        let mut session = ServerSession::new();
+        let mut buffer = [0; 16];
+
+        let bytes = session.greeting(Greeting {
+            code: None,
+            comment: "POP3 server ready".into(),
+            timestamp: None,
+        });
+        stream.write_all(&bytes).await.unwrap();
+
+        while !session.is_closed() {
+            let command = match session.next_event().unwrap() {
+                Some(ServerEvent::Command(command)) => command,
+                Some(ServerEvent::Rejected { response, .. }) => {
+                    stream.write_all(&response).await.unwrap();
+                    continue;
+                }
+                Some(ServerEvent::AuthData(_)) => unreachable!(),
+                None => {
+                    let n = stream.read(&mut buffer).await.unwrap();
+                    session.receive(&buffer[..n]);
+                    continue;
+                }
+            };
+
+            let bytes = match command {
+                Command::User(_) | Command::Dele { .. } | Command::Quit => {
+                    session.respond(ok("")).unwrap()
+                }
+                Command::Pass(password) if password == "secret" => {
+                    session.respond(ok("maildrop locked")).unwrap()
+                }
+                Command::Stat => session
+                    .respond(Response::Ok(DropListing {
+                        message_count: 2,
+                        maildrop_size: 320,
+                        trailing: None,
+                    }))
+                    .unwrap(),
+                Command::ListAll => session
+                    .respond(multi_line(vec![
+                        ScanListing {
+                            message_id: MessageNumber::new(1).unwrap(),
+                            message_size: 120,
+                            trailing: None,
+                        },
+                        ScanListing {
+                            message_id: MessageNumber::new(2).unwrap(),
+                            message_size: 200,
+                            trailing: None,
+                        },
+                    ]))
+                    .unwrap(),
+                Command::UidlAll => session
+                    .respond(multi_line(vec![UniqueIdListing {
+                        message_id: MessageNumber::new(1).unwrap(),
+                        message_uid: "whqtswO00WBw418f9t5JxYwZ".into(),
+                        trailing: None,
+                    }]))
+                    .unwrap(),
+                Command::Retr { msg } if msg.get() == 1 => session
+                    .respond(multi_line::<Cow<[u8]>>(vec![
+                        b"Subject: Test"[..].into(),
+                        b""[..].into(),
+                        b".Hello!"[..].into(),
+                    ]))
+                    .unwrap(),
+                _ => session
+                    .respond(Response::<SingleLine, _>::Err(SingleLine {
+                        code: None,
+                        comment: "no such message".into(),
+                    }))
+                    .unwrap(),
+            };
+
+            stream.write_all(&bytes).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_client() {
+        let (client, server) = duplex(64);
+        let server = tokio::spawn(mock_server(server));
+
+        let mut client = AsyncClient::connect(client).await.unwrap();
+        assert_eq!(client.greeting().comment, "POP3 server ready");
+
+        assert!(matches!(
+            client.login("mrose", "wrong").await,
+            Err(ClientError::Negative(_))
+        ));
+        client.login("mrose", "secret").await.unwrap();
+
+        assert_eq!(
+            client.stat().await.unwrap(),
+            DropListing {
+                message_count: 2,
+                maildrop_size: 320,
+                trailing: None,
+            }
+        );
+        assert_eq!(client.list_all().await.unwrap().len(), 2);
+        assert_eq!(
+            client.uidl_all().await.unwrap()[0].message_uid,
+            "whqtswO00WBw418f9t5JxYwZ"
+        );
+        assert_eq!(
+            client.retr(MessageNumber::new(1).unwrap()).await.unwrap(),
+            b"Subject: Test\r\n\r\n.Hello!\r\n"
+        );
+        assert!(matches!(
+            client.retr(MessageNumber::new(3).unwrap()).await,
+            Err(ClientError::Negative(_))
+        ));
+        client.dele(MessageNumber::new(1).unwrap()).await.unwrap();
+        client.quit().await.unwrap();
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connection_closed() {
+        let (client, mut server) = duplex(64);
+
+        server
+            .write_all(b"+OK POP3 server ready\r\n")
+            .await
+            .unwrap();
+        let mut client = AsyncClient::connect(client).await.unwrap();
+        drop(server);
+
+        assert!(matches!(
+            client.user("alice").await,
+            Err(ClientError::Io(_)) | Err(ClientError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled() {
+        let (client, mut server) = duplex(64);
+
+        server
+            .write_all(b"+OK POP3 server ready\r\n")
+            .await
+            .unwrap();
+        let mut client = AsyncClient::connect(client).await.unwrap();
+
+        // USER is sent, but the call is cancelled before the response is received.
+        tokio::select! {
+            biased;
+            _ = client.user("alice") => panic!("no response was sent"),
+            _ = std::future::ready(()) => {}
+        }
+        server.write_all(b"+OK\r\n").await.unwrap();
+
+        assert!(matches!(
+            client.pass("secret").await,
+            Err(ClientError::Desync)
+        ));
+    }
+}
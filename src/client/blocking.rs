@@ -0,0 +1,617 @@
+use std::{
+    borrow::Cow,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{
+    client::ClientError,
+    error::{DecodeError, DecodeResult},
+    parse::{
+        greeting, response_apop, response_auth, response_capa, response_dele, response_list,
+        response_list_all, response_noop, response_pass, response_quit, response_retr,
+        response_rset, response_stat, response_top, response_uidl, response_uidl_all,
+        response_user,
+    },
+    types::{
+        AuthData, AuthResponse, Capability, Command, DropListing, Greeting, IntoOwned, Mechanism,
+        MessageNumber, MultiLine, Response, ScanListing, SingleLine, UniqueIdListing,
+    },
+};
+
+/// Response to RETR or TOP.
+type Message = Response<MultiLine<'static, Cow<'static, [u8]>>, SingleLine<'static>>;
+
+/// Blocking POP3 client over any `BufRead + Write` transport.
+///
+/// Every command method returns the parsed response, i.e., negative responses are not turned into
+/// errors. The responses own their data, i.e., they do not borrow from the client. Use
+/// [TcpConnection] to connect via TCP with read and write timeouts.
+///
+/// Note: After an error (e.g., a timeout), the client does not know where the next response starts.
+/// All further commands fail with [ClientError::Desync].
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use pop3_codec::{client::BlockingClient, types::Response};
+///
+/// let mut client =
+///     BlockingClient::connect_tcp("127.0.0.1:110", Some(Duration::from_secs(30))).unwrap();
+///
+/// client.user("alice").unwrap();
+/// client.pass("secret").unwrap();
+///
+/// if let Response::Ok(listing) = client.stat().unwrap() {
+///     println!("{} messages", listing.message_count);
+/// }
+///
+/// client.quit().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct BlockingClient<S> {
+    stream: S,
+    greeting: Greeting<'static>,
+    buffer: Vec<u8>,
+    /// A previous read or write failed.
+    desynced: bool,
+}
+
+impl BlockingClient<TcpConnection> {
+    /// Connects to `addr` and reads the greeting.
+    ///
+    /// The `timeout` is used for connecting as well as for every read and write.
+    pub fn connect_tcp<A>(addr: A, timeout: Option<Duration>) -> Result<Self, ClientError>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::connect(TcpConnection::connect(addr, timeout)?)
+    }
+}
+
+impl<S> BlockingClient<S>
+where
+    S: BufRead + Write,
+{
+    /// Reads the greeting from `stream`.
+    pub fn connect(stream: S) -> Result<Self, ClientError> {
+        let mut client = Self {
+            stream,
+            greeting: Greeting {
+                code: None,
+                comment: "".into(),
+                timestamp: None,
+            },
+            buffer: Vec::new(),
+            desynced: false,
+        };
+
+        client.greeting = client.read(|input| owned(greeting(input)))?;
+
+        Ok(client)
+    }
+
+    /// Returns the greeting of the server.
+    pub fn greeting(&self) -> &Greeting<'static> {
+        &self.greeting
+    }
+
+    /// Returns a reference to the underlying transport, e.g., to change its timeouts.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the underlying transport.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    pub fn user(
+        &mut self,
+        name: &str,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::User(name.into()), |input| {
+            owned(response_user(input))
+        })
+    }
+
+    pub fn pass(
+        &mut self,
+        password: &str,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Pass(password.into()), |input| {
+            owned(response_pass(input))
+        })
+    }
+
+    pub fn apop(
+        &mut self,
+        name: &str,
+        digest: &str,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        let command = Command::Apop {
+            name: name.into(),
+            digest: digest.into(),
+        };
+
+        self.execute(&command, |input| owned(response_apop(input)))
+    }
+
+    /// Authenticates using AUTH (RFC 5034).
+    ///
+    /// Every (base64 encoded) challenge of the server is passed to `respond`.
+    pub fn auth<'r, F>(
+        &mut self,
+        mechanism: Mechanism,
+        initial_response: Option<String>,
+        mut respond: F,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError>
+    where
+        F: FnMut(&str) -> AuthData<'r>,
+    {
+        let command = Command::Auth {
+            mechanism,
+            initial_response: initial_response.map(Cow::Owned),
+        };
+
+        let mut response = self.execute(&command, |input| owned(response_auth(input)))?;
+
+        loop {
+            match response {
+                AuthResponse::Continue(challenge) => {
+                    self.write(&respond(&challenge).serialize())?;
+                    response = self.read(|input| owned(response_auth(input)))?;
+                }
+                AuthResponse::Done(response) => return Ok(response),
+            }
+        }
+    }
+
+    pub fn capa(
+        &mut self,
+    ) -> Result<Response<MultiLine<'static, Capability<'static>>, SingleLine<'static>>, ClientError>
+    {
+        self.execute(&Command::Capa, |input| owned(response_capa(input)))
+    }
+
+    pub fn stat(
+        &mut self,
+    ) -> Result<Response<DropListing<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Stat, |input| owned(response_stat(input)))
+    }
+
+    pub fn list_all(
+        &mut self,
+    ) -> Result<Response<MultiLine<'static, ScanListing<'static>>, SingleLine<'static>>, ClientError>
+    {
+        self.execute(&Command::ListAll, |input| owned(response_list_all(input)))
+    }
+
+    pub fn list(
+        &mut self,
+        msg: MessageNumber,
+    ) -> Result<Response<ScanListing<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::List { msg }, |input| owned(response_list(input)))
+    }
+
+    pub fn uidl_all(
+        &mut self,
+    ) -> Result<
+        Response<MultiLine<'static, UniqueIdListing<'static>>, SingleLine<'static>>,
+        ClientError,
+    > {
+        self.execute(&Command::UidlAll, |input| owned(response_uidl_all(input)))
+    }
+
+    pub fn uidl(
+        &mut self,
+        msg: MessageNumber,
+    ) -> Result<Response<UniqueIdListing<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Uidl { msg }, |input| owned(response_uidl(input)))
+    }
+
+    pub fn retr(&mut self, msg: MessageNumber) -> Result<Message, ClientError> {
+        self.execute(&Command::Retr { msg }, |input| owned(response_retr(input)))
+    }
+
+    pub fn top(&mut self, msg: MessageNumber, n: u32) -> Result<Message, ClientError> {
+        self.execute(&Command::Top { msg, n }, |input| owned(response_top(input)))
+    }
+
+    pub fn dele(
+        &mut self,
+        msg: MessageNumber,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Dele { msg }, |input| owned(response_dele(input)))
+    }
+
+    pub fn noop(
+        &mut self,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Noop, |input| owned(response_noop(input)))
+    }
+
+    pub fn rset(
+        &mut self,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Rset, |input| owned(response_rset(input)))
+    }
+
+    /// Ends the session, i.e., the server removes messages marked as deleted.
+    pub fn quit(
+        mut self,
+    ) -> Result<Response<SingleLine<'static>, SingleLine<'static>>, ClientError> {
+        self.execute(&Command::Quit, |input| owned(response_quit(input)))
+    }
+
+    /// Sends `command` and reads the response using `parser`.
+    ///
+    /// This can be used for commands without a dedicated method. The result of `parser` must not
+    /// borrow from its input, i.e., use [IntoOwned::into_owned] when needed.
+    pub fn execute<T, F>(&mut self, command: &Command<'_>, parser: F) -> Result<T, ClientError>
+    where
+        F: for<'a> Fn(&'a [u8]) -> DecodeResult<'a, T>,
+    {
+        if self.desynced {
+            return Err(ClientError::Desync);
+        }
+
+        self.write(&command.serialize())?;
+        self.read(parser)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ClientError> {
+        let result = self
+            .stream
+            .write_all(bytes)
+            .and_then(|_| self.stream.flush());
+
+        if result.is_err() {
+            self.desynced = true;
+        }
+
+        Ok(result?)
+    }
+
+    fn read<T, F>(&mut self, parser: F) -> Result<T, ClientError>
+    where
+        F: for<'a> Fn(&'a [u8]) -> DecodeResult<'a, T>,
+    {
+        let result = self.read_response(parser);
+
+        if result.is_err() {
+            self.desynced = true;
+        }
+
+        result
+    }
+
+    fn read_response<T, F>(&mut self, parser: F) -> Result<T, ClientError>
+    where
+        F: for<'a> Fn(&'a [u8]) -> DecodeResult<'a, T>,
+    {
+        // Start of the first line of the buffer, which was not checked by `may_end` yet.
+        let mut scanned = 0;
+
+        loop {
+            // The buffer is only parsed (again) when the response may be complete. This avoids
+            // parsing a long multi-line response again and again.
+            if scanned == 0 || may_end(&self.buffer, &mut scanned) {
+                match parser(&self.buffer) {
+                    Ok((rem, item)) => {
+                        let consumed = self.buffer.len() - rem.len();
+                        self.buffer.drain(..consumed);
+
+                        return Ok(item);
+                    }
+                    Err(DecodeError::Incomplete { .. }) => {}
+                    Err(error) => return Err(ClientError::Decode(error)),
+                }
+
+                may_end(&self.buffer, &mut scanned);
+            }
+
+            let chunk = self.stream.fill_buf()?;
+
+            if chunk.is_empty() {
+                return Err(ClientError::ConnectionClosed);
+            }
+
+            let length = chunk.len();
+            self.buffer.extend_from_slice(chunk);
+            self.stream.consume(length);
+        }
+    }
+}
+
+/// Returns `true` when a line, which may end a response, was completed since `scanned`, i.e., the
+/// first line (status line) or a line containing only ".". Afterwards, `scanned` points to the
+/// start of the incomplete last line.
+fn may_end(buffer: &[u8], scanned: &mut usize) -> bool {
+    let mut found = false;
+
+    while let Some(offset) = buffer[*scanned..].iter().position(|byte| *byte == b'\n') {
+        let end = *scanned + offset + 1;
+        let line = &buffer[*scanned..end];
+
+        if *scanned == 0 || line == b".\r\n" || line == b".\n" {
+            found = true;
+        }
+
+        *scanned = end;
+    }
+
+    found
+}
+
+/// Detaches a parsed item from the buffer of the client.
+fn owned<T>(result: DecodeResult<'_, T>) -> DecodeResult<'_, T::Owned>
+where
+    T: IntoOwned,
+{
+    result.map(|(rem, item)| (rem, item.into_owned()))
+}
+
+/// Buffered TCP transport with read and write timeouts.
+#[derive(Debug)]
+pub struct TcpConnection {
+    reader: BufReader<TcpStream>,
+}
+
+impl TcpConnection {
+    /// Connects to `addr`.
+    ///
+    /// The `timeout` is used for connecting as well as for every read and write.
+    pub fn connect<A>(addr: A, timeout: Option<Duration>) -> std::io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let stream = match timeout {
+            Some(timeout) => {
+                let mut last_error = None;
+                let mut stream = None;
+
+                for addr in addr.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(connected) => {
+                            stream = Some(connected);
+                            break;
+                        }
+                        Err(error) => last_error = Some(error),
+                    }
+                }
+
+                match (stream, last_error) {
+                    (Some(stream), _) => stream,
+                    (None, Some(error)) => return Err(error),
+                    (None, None) => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "could not resolve to any address",
+                        ))
+                    }
+                }
+            }
+            None => TcpStream::connect(addr)?,
+        };
+
+        Self::new(stream, timeout, timeout)
+    }
+
+    /// Wraps an already connected `stream`.
+    pub fn new(
+        stream: TcpStream,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> std::io::Result<Self> {
+        stream.set_read_timeout(read_timeout)?;
+        stream.set_write_timeout(write_timeout)?;
+
+        Ok(Self {
+            reader: BufReader::new(stream),
+        })
+    }
+
+    /// Returns the underlying stream, e.g., to change its timeouts.
+    pub fn get_ref(&self) -> &TcpStream {
+        self.reader.get_ref()
+    }
+}
+
+impl Read for TcpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for TcpConnection {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+impl Write for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.reader.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.reader.get_mut().flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, net::TcpListener};
+
+    use super::*;
+
+    /// Transport, which replays `input` and records the output.
+    struct Mock {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Mock {
+        fn new(input: &[u8]) -> Self {
+            Self {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl BufRead for Mock {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            // Return a single byte at a time to test buffering.
+            let position = self.input.position() as usize;
+            let input = self.input.get_ref();
+
+            Ok(&input[position.min(input.len())..(position + 1).min(input.len())])
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.input.consume(amt)
+        }
+    }
+
+    impl Write for Mock {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_blocking_client() {
+        let mock = Mock::new(
+            b"+OK POP3 server ready <1896.697170952@dbc.mtview.ca.us>\r\n\
+              +OK mrose's maildrop has 2 messages (320 octets)\r\n\
+              +OK 2 320\r\n\
+              +OK 2 messages (320 octets)\r\n1 120\r\n2 200\r\n.\r\n\
+              +OK 120 octets\r\nSubject: Test\r\n\r\n..Hello!\r\n.\r\n\
+              -ERR no such message\r\n",
+        );
+
+        let mut client = BlockingClient::connect(mock).unwrap();
+        assert_eq!(
+            client.greeting().timestamp.as_deref(),
+            Some("1896.697170952@dbc.mtview.ca.us")
+        );
+
+        assert!(matches!(
+            client.apop("mrose", "c4c9334bac560ecc979e58001b3e22fb"),
+            Ok(Response::Ok(_))
+        ));
+        assert_eq!(
+            client.stat().unwrap(),
+            Response::Ok(DropListing {
+                message_count: 2,
+                maildrop_size: 320,
+                trailing: None,
+            })
+        );
+        match client.list_all().unwrap() {
+            Response::Ok(listing) => assert_eq!(listing.body.len(), 2),
+            Response::Err(_) => panic!(),
+        }
+        match client.retr(MessageNumber::new(1).unwrap()).unwrap() {
+            Response::Ok(message) => {
+                assert_eq!(message.body_bytes(), b"Subject: Test\r\n\r\n.Hello!\r\n")
+            }
+            Response::Err(_) => panic!(),
+        }
+        assert!(matches!(
+            client.dele(MessageNumber::new(3).unwrap()),
+            Ok(Response::Err(_))
+        ));
+        assert!(matches!(client.noop(), Err(ClientError::ConnectionClosed)));
+
+        assert_eq!(
+            client.into_inner().output,
+            b"APOP mrose c4c9334bac560ecc979e58001b3e22fb\r\nSTAT\r\nLIST\r\nRETR 1\r\nDELE 3\r\nNOOP\r\n"
+        );
+    }
+
+    #[test]
+    fn test_may_end() {
+        let mut scanned = 0;
+        assert!(!may_end(b"+OK", &mut scanned));
+        assert_eq!(scanned, 0);
+        assert!(may_end(b"+OK\r\nHello", &mut scanned));
+        assert_eq!(scanned, 5);
+        assert!(!may_end(b"+OK\r\nHello\r\n.", &mut scanned));
+        assert_eq!(scanned, 12);
+        assert!(may_end(b"+OK\r\nHello\r\n.\r\n", &mut scanned));
+        assert_eq!(scanned, 15);
+    }
+
+    #[test]
+    fn test_blocking_client_auth() {
+        let mock = Mock::new(b"+OK\r\n+ \r\n+OK maildrop locked\r\n");
+
+        let mut client = BlockingClient::connect(mock).unwrap();
+        let response = client
+            .auth(Mechanism::Plain, None, |challenge| {
+                assert_eq!(challenge, "");
+                AuthData::Response("dGVzdAB0ZXN0AHRlc3Q=".into())
+            })
+            .unwrap();
+        assert!(matches!(response, Response::Ok(_)));
+
+        assert_eq!(
+            client.into_inner().output,
+            b"AUTH PLAIN\r\ndGVzdAB0ZXN0AHRlc3Q=\r\n"
+        );
+    }
+
+    #[test]
+    fn test_blocking_client_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"+OK POP3 server ready\r\n").unwrap();
+
+            // Never answer, but keep the connection open until the client gave up.
+            let mut buffer = [0; 16];
+            while let Ok(n) = stream.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+            }
+        });
+
+        let mut client =
+            BlockingClient::connect_tcp(addr, Some(Duration::from_millis(100))).unwrap();
+
+        match client.noop() {
+            Err(ClientError::Io(error)) => assert!(matches!(
+                error.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            )),
+            other => panic!("{:?}", other),
+        }
+
+        // The response may still arrive, i.e., it would be mistaken for the next response.
+        assert!(matches!(client.noop(), Err(ClientError::Desync)));
+
+        drop(client);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! POP3 clients performing I/O.
+//!
+//! The [BlockingClient] uses `std::io` and returns the parsed responses. It calls the
+//! [parsers](crate::parse) directly, i.e., it does not track the state of the session. The
+//! [AsyncClient] uses tokio, is built on top of the [ClientSession](crate::session::ClientSession),
+//! and turns negative responses into errors.
+//!
+//! Note: The async client (and the dependency on tokio) is gated by the "tokio" feature.
+
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    error::DecodeError,
+    session::SessionError,
+    types::{Response, SingleLine},
+};
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+mod blocking;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncClient;
+pub use blocking::{BlockingClient, TcpConnection};
+
+/// Error returned by a client.
+#[derive(Debug)]
+pub enum ClientError {
+    Io(std::io::Error),
+    /// The session rejected a command or the received data.
+    Session(SessionError),
+    /// Received data could not be parsed.
+    Decode(DecodeError),
+    /// The server answered with "-ERR".
+    Negative(SingleLine<'static>),
+    /// The server closed the connection unexpectedly.
+    ConnectionClosed,
+    /// The client is out of sync with the server, e.g., because a previous call failed or was
+    /// cancelled while it was waiting for a response. The connection can not be used anymore.
+    Desync,
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Io(error) => write!(f, "I/O error: {}", error),
+            ClientError::Session(error) => write!(f, "{}", error),
+            ClientError::Decode(error) => write!(f, "received data is malformed: {}", error),
+            ClientError::Negative(response) => {
+                write!(f, "server responded with -ERR: {}", response.comment)
+            }
+            ClientError::ConnectionClosed => write!(f, "connection closed by server"),
+            ClientError::Desync => write!(f, "client is out of sync with server"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(error) => Some(error),
+            ClientError::Session(error) => Some(error),
+            ClientError::Decode(error) => Some(error),
+            ClientError::Negative(_) | ClientError::ConnectionClosed | ClientError::Desync => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ClientError {
+    fn from(error: std::io::Error) -> Self {
+        ClientError::Io(error)
+    }
+}
+
+impl From<SessionError> for ClientError {
+    fn from(error: SessionError) -> Self {
+        ClientError::Session(error)
+    }
+}
+
+/// Turns a negative response into an error.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+fn positive<O>(response: Response<O, SingleLine<'static>>) -> Result<O, ClientError>
+where
+    O: std::fmt::Debug + Clone + PartialEq + Eq,
+{
+    match response {
+        Response::Ok(ok) => Ok(ok),
+        Response::Err(err) => Err(ClientError::Negative(err)),
+    }
+}
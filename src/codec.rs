@@ -0,0 +1,427 @@
+//! Codecs for [tokio_util::codec], e.g., to be used with `Framed`.
+//!
+//! The [ClientCodec] encodes commands and decodes responses. It remembers the sent commands to
+//! select the matching response parser. The [ServerCodec] decodes commands and encodes responses.
+//!
+//! Note: This module (and the dependency on tokio-util) is gated by the "tokio" feature.
+
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    error::{DecodeError, DecodeResult},
+    parse::ParseOptions,
+    types::{AnyResponse, AuthData, AuthResponse, Command, IntoOwned},
+};
+
+/// Codec for the client side, i.e., it encodes commands and decodes responses.
+///
+/// The first decoded item is the greeting. Afterwards, every response is parsed according to the
+/// oldest command, which was not answered yet. A challenge sent in response to AUTH does not
+/// answer the command, i.e., the client must encode [AuthData] until the exchange is done.
+#[derive(Clone, Debug, Default)]
+pub struct ClientCodec {
+    greeted: bool,
+    pending: VecDeque<Command<'static>>,
+    options: ParseOptions,
+}
+
+impl ClientCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the greeting and the responses with `options` instead of the default ones.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the commands, which were not answered yet (oldest first).
+    pub fn pending(&self) -> impl Iterator<Item = &Command<'static>> {
+        self.pending.iter()
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = AnyResponse<'static>;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        let result = match (self.greeted, self.pending.front()) {
+            (false, _) => advance(src, |input| {
+                self.options
+                    .greeting(input)
+                    .map(|(rem, greeting)| (rem, AnyResponse::Greeting(greeting.into_owned())))
+            }),
+            (true, Some(command)) => advance(src, |input| {
+                self.options
+                    .response(command, input)
+                    .map(|(rem, response)| (rem, response.into_owned()))
+            }),
+            (true, None) => return Err(CodecError::UnexpectedData),
+        };
+
+        let response = match result? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+
+        match response {
+            AnyResponse::Greeting(_) => self.greeted = true,
+            AnyResponse::Auth(AuthResponse::Continue(_)) => {}
+            _ => {
+                self.pending.pop_front();
+            }
+        }
+
+        Ok(Some(response))
+    }
+}
+
+impl<'a> Encoder<Command<'a>> for ClientCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Command<'a>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item.serialize());
+        self.pending.push_back(item.into_owned());
+
+        Ok(())
+    }
+}
+
+impl<'a> Encoder<AuthData<'a>> for ClientCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: AuthData<'a>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item.serialize());
+
+        Ok(())
+    }
+}
+
+/// Codec for the server side, i.e., it decodes commands and encodes responses.
+///
+/// After a challenge was encoded, the next line is decoded as [AuthData]. Lines, which can not
+/// be parsed, are skipped and returned as [ClientMessage::Invalid], so that the server can
+/// answer them with "-ERR" and continue.
+#[derive(Clone, Debug, Default)]
+pub struct ServerCodec {
+    challenged: bool,
+    /// The current line is invalid and is dropped until its end is received.
+    skipping: Option<DecodeError>,
+    options: ParseOptions,
+}
+
+/// Item decoded by the [ServerCodec].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientMessage {
+    Command(Command<'static>),
+    /// The answer of the client to a challenge.
+    AuthData(AuthData<'static>),
+    /// A line, which could not be parsed.
+    Invalid(DecodeError),
+}
+
+impl ServerCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the commands with `options` instead of the default ones.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Drops the current line and returns the error as soon as its end was received.
+    ///
+    /// This bounds the buffer, because the invalid line is not kept in memory.
+    fn skip_line(&mut self, src: &mut BytesMut, error: DecodeError) -> Option<ClientMessage> {
+        match src.iter().position(|byte| *byte == b'\n') {
+            Some(end) => {
+                src.advance(end + 1);
+                self.skipping = None;
+
+                Some(ClientMessage::Invalid(error))
+            }
+            None => {
+                src.clear();
+                self.skipping = Some(error);
+
+                None
+            }
+        }
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = ClientMessage;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(error) = self.skipping.take() {
+            return Ok(self.skip_line(src, error));
+        }
+
+        let result = if self.challenged {
+            advance(src, |input| {
+                self.options
+                    .auth_data(input)
+                    .map(|(rem, data)| (rem, ClientMessage::AuthData(data.into_owned())))
+            })
+        } else {
+            advance(src, |input| {
+                self.options
+                    .command(input)
+                    .map(|(rem, command)| (rem, ClientMessage::Command(command.into_owned())))
+            })
+        };
+
+        match result {
+            Ok(Some(message)) => {
+                if let ClientMessage::AuthData(_) = message {
+                    self.challenged = false;
+                }
+
+                Ok(Some(message))
+            }
+            Ok(None) => Ok(None),
+            Err(CodecError::Decode(error)) => Ok(self.skip_line(src, error)),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl<'a> Encoder<AnyResponse<'a>> for ServerCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: AnyResponse<'a>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.challenged = matches!(item, AnyResponse::Auth(AuthResponse::Continue(_)));
+        dst.extend_from_slice(&item.serialize());
+
+        Ok(())
+    }
+}
+
+/// Error returned by the codecs.
+#[derive(Debug)]
+pub enum CodecError {
+    Io(std::io::Error),
+    /// Received data could not be parsed.
+    Decode(DecodeError),
+    /// Data was received, but no response was expected.
+    UnexpectedData,
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Io(error) => write!(f, "I/O error: {}", error),
+            CodecError::Decode(error) => write!(f, "received data is malformed: {}", error),
+            CodecError::UnexpectedData => write!(f, "received data without a pending command"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(error) => Some(error),
+            CodecError::Decode(error) => Some(error),
+            CodecError::UnexpectedData => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CodecError {
+    fn from(error: std::io::Error) -> Self {
+        CodecError::Io(error)
+    }
+}
+
+/// Parses `src`, drops the consumed bytes, and returns the parsed item (if complete).
+fn advance<T, F>(src: &mut BytesMut, parse: F) -> Result<Option<T>, CodecError>
+where
+    F: for<'a> FnOnce(&'a [u8]) -> DecodeResult<'a, T>,
+{
+    let result = parse(src).map(|(rem, item)| (rem.len(), item));
+
+    match result {
+        Ok((remaining, item)) => {
+            src.advance(src.len() - remaining);
+
+            Ok(Some(item))
+        }
+        Err(DecodeError::Incomplete { .. }) => Ok(None),
+        Err(error) => Err(CodecError::Decode(error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{DropListing, Mechanism, Response, SingleLine};
+
+    #[test]
+    fn test_client_codec() {
+        let mut codec = ClientCodec::new();
+        let mut src = BytesMut::new();
+        let mut dst = BytesMut::new();
+
+        codec.encode(Command::Stat, &mut dst).unwrap();
+        codec.encode(Command::ListAll, &mut dst).unwrap();
+        assert_eq!(&dst[..], b"STAT\r\nLIST\r\n");
+
+        let input = b"+OK POP3 server ready\r\n+OK 2 320\r\n+OK\r\n1 120\r\n2 200\r\n.\r\n";
+        let mut responses = Vec::new();
+
+        // Feed the input byte by byte to test buffering.
+        for byte in input.iter() {
+            src.extend_from_slice(&[*byte]);
+
+            while let Some(response) = codec.decode(&mut src).unwrap() {
+                responses.push(response);
+            }
+        }
+
+        assert!(src.is_empty());
+        assert_eq!(responses.len(), 3);
+        assert!(matches!(responses[0], AnyResponse::Greeting(_)));
+        assert_eq!(
+            responses[1],
+            AnyResponse::Stat(Response::Ok(DropListing {
+                message_count: 2,
+                maildrop_size: 320,
+                trailing: None,
+            }))
+        );
+        assert!(matches!(
+            responses[2],
+            AnyResponse::ListAll(Response::Ok(_))
+        ));
+        assert_eq!(codec.pending().count(), 0);
+
+        src.extend_from_slice(b"+OK\r\n");
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(CodecError::UnexpectedData)
+        ));
+    }
+
+    #[test]
+    fn test_client_codec_auth() {
+        let mut codec = ClientCodec::new();
+        let mut src = BytesMut::from(&b"+OK\r\n+ \r\n+OK\r\n"[..]);
+        let mut dst = BytesMut::new();
+
+        codec
+            .encode(
+                Command::Auth {
+                    mechanism: Mechanism::Plain,
+                    initial_response: None,
+                },
+                &mut dst,
+            )
+            .unwrap();
+
+        codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(AnyResponse::Auth(AuthResponse::Continue("".into())))
+        );
+
+        codec
+            .encode(AuthData::Response("dGVzdAB0ZXN0AHRlc3Q=".into()), &mut dst)
+            .unwrap();
+        assert_eq!(&dst[..], b"AUTH PLAIN\r\ndGVzdAB0ZXN0AHRlc3Q=\r\n");
+
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(AnyResponse::Auth(AuthResponse::Done(Response::Ok(_))))
+        ));
+        assert_eq!(codec.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_server_codec() {
+        let mut codec = ServerCodec::new();
+        let mut src = BytesMut::from(&b"USER alice\r\nFOO\r\nAUTH PLAIN\r\n*\r\nQU"[..]);
+        let mut dst = BytesMut::new();
+
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Command(Command::User("alice".into())))
+        );
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Invalid(DecodeError::Failed { .. }))
+        ));
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Command(Command::Auth { .. }))
+        ));
+
+        codec
+            .encode(
+                AnyResponse::Auth(AuthResponse::Continue("".into())),
+                &mut dst,
+            )
+            .unwrap();
+        assert_eq!(&dst[..], b"+ \r\n");
+
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::AuthData(AuthData::Cancel))
+        );
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"IT\r\n");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Command(Command::Quit))
+        );
+
+        codec
+            .encode(
+                AnyResponse::Quit(Response::Ok(SingleLine {
+                    code: None,
+                    comment: "bye".into(),
+                })),
+                &mut dst,
+            )
+            .unwrap();
+        assert_eq!(&dst[..], b"+ \r\n+OK bye\r\n");
+    }
+
+    #[test]
+    fn test_server_codec_line_too_long() {
+        let mut codec = ServerCodec::new();
+        let mut src = BytesMut::from(&b"USER "[..]);
+        src.extend_from_slice(&[b'a'; 300]);
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert!(src.is_empty());
+
+        src.extend_from_slice(b"aaa\r\nNOOP\r\n");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Invalid(DecodeError::LineTooLong {
+                limit: 255
+            }))
+        );
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(ClientMessage::Command(Command::Noop))
+        );
+    }
+}
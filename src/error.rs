@@ -0,0 +1,54 @@
+use std::{
+    fmt::{Display, Formatter},
+    num::NonZeroUsize,
+};
+
+/// Result of a (public) parser.
+///
+/// On success, the remaining input and the parsed value are returned.
+pub type DecodeResult<'a, T> = Result<(&'a [u8], T), DecodeError>;
+
+/// Error returned by a (public) parser.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// More data is required to decide.
+    ///
+    /// The input must be passed again after more data was received.
+    Incomplete {
+        /// Number of additional bytes required (if known).
+        needed: Option<NonZeroUsize>,
+    },
+    /// The input could not be parsed.
+    Failed {
+        /// Position of the error relative to the input.
+        offset: usize,
+        /// Description of what was expected at `offset`.
+        expected: &'static str,
+    },
+    /// The line exceeds the maximum length allowed by the RFC.
+    LineTooLong {
+        /// Maximum length (including CRLF).
+        limit: usize,
+    },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Incomplete {
+                needed: Some(needed),
+            } => {
+                write!(f, "incomplete input ({} more bytes needed)", needed)
+            }
+            DecodeError::Incomplete { needed: None } => write!(f, "incomplete input"),
+            DecodeError::Failed { offset, expected } => {
+                write!(f, "failed at offset {} (expected {})", offset, expected)
+            }
+            DecodeError::LineTooLong { limit } => {
+                write!(f, "line exceeds the maximum length of {} octets", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
@@ -1,4 +1,10 @@
+pub mod client;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod error;
 pub mod parse;
+pub mod server;
+pub mod session;
 pub mod types;
 #[cfg(feature = "utils")]
 pub mod utils;
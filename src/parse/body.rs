@@ -0,0 +1,273 @@
+use crate::{
+    error::DecodeError,
+    parse::{
+        finish,
+        response::{head, single_line},
+        ParseOptions, MAX_RESPONSE_LINE,
+    },
+    types::response::{Response, SingleLine},
+};
+
+/// Incremental decoder for the response to RETR or TOP.
+///
+/// In contrast to [response_retr](crate::parse::response_retr), the decoder does not collect the
+/// message but yields chunks of the (unstuffed) body as soon as they arrive. Thus, memory usage is
+/// bounded regardless of the size of the message.
+///
+/// The decoder is used by calling [decode](BodyDecoder::decode) repeatedly. Every call returns the
+/// number of consumed bytes and (optionally) an event. Consumed bytes must be dropped by the caller.
+/// Unconsumed bytes must be passed again in the next call, followed by more data (if any).
+///
+/// ```rust
+/// use pop3_codec::parse::{BodyDecoder, BodyEvent};
+///
+/// let mut decoder = BodyDecoder::new();
+/// let mut input: &[u8] = b"+OK 7 octets\r\n..foo\r\n.\r\n";
+/// let mut body = Vec::new();
+///
+/// while !decoder.is_done() {
+///     let (consumed, event) = decoder.decode(input).unwrap();
+///     input = &input[consumed..];
+///
+///     if let Some(BodyEvent::Data(data)) = event {
+///         body.extend_from_slice(data);
+///     }
+/// }
+///
+/// assert_eq!(body, b".foo\r\n");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BodyDecoder {
+    state: BodyState,
+    options: ParseOptions,
+}
+
+/// Event produced by the [BodyDecoder].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BodyEvent<'a> {
+    /// The status line of the response.
+    ///
+    /// Note: A negative response has no body, i.e., the decoder is done afterwards.
+    Head(Response<SingleLine<'a>, SingleLine<'a>>),
+    /// A chunk of the unstuffed body (including line endings).
+    Data(&'a [u8]),
+    /// The terminating "." line was received.
+    Done,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum BodyState {
+    /// Waiting for the status line.
+    #[default]
+    Head,
+    /// At the start of a line.
+    LineStart,
+    /// In the middle of a line.
+    Middle,
+    /// After a "." at the start of a line.
+    Dot,
+    /// After a "." and CR at the start of a line.
+    DotCr,
+    /// The response is complete.
+    Done,
+}
+
+impl BodyDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the status line with `options` instead of the default ones.
+    ///
+    /// Note: The body is not affected, i.e., bare LF line endings are always accepted.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns `true` when the response is complete.
+    pub fn is_done(&self) -> bool {
+        self.state == BodyState::Done
+    }
+
+    /// Decodes the next event from `input`.
+    ///
+    /// Returns the number of consumed bytes and the event (if any). No event is returned when
+    /// more input is required. After the response is complete, no more bytes are consumed.
+    pub fn decode<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<(usize, Option<BodyEvent<'a>>), DecodeError> {
+        match self.state {
+            BodyState::Head => match finish(input, self.options.limit(MAX_RESPONSE_LINE), |input| {
+                single_line(input, &self.options, |i| head(i, &self.options), false)
+            }) {
+                Ok((rem, response)) => {
+                    self.state = match response {
+                        Response::Ok(_) => BodyState::LineStart,
+                        Response::Err(_) => BodyState::Done,
+                    };
+
+                    Ok((input.len() - rem.len(), Some(BodyEvent::Head(response))))
+                }
+                Err(DecodeError::Incomplete { .. }) => Ok((0, None)),
+                Err(error) => Err(error),
+            },
+            BodyState::Done => Ok((0, None)),
+            _ => Ok(self.decode_body(input)),
+        }
+    }
+
+    fn decode_body<'a>(&mut self, input: &'a [u8]) -> (usize, Option<BodyEvent<'a>>) {
+        // Start of the data which was not returned yet.
+        let mut start = 0;
+
+        for (index, byte) in input.iter().copied().enumerate() {
+            match self.state {
+                BodyState::LineStart => match byte {
+                    b'.' => {
+                        // Return pending data first, because the "." must be dropped.
+                        if index > start {
+                            return (index, Some(BodyEvent::Data(&input[start..index])));
+                        }
+
+                        self.state = BodyState::Dot;
+                        start = index + 1;
+                    }
+                    b'\n' => {}
+                    _ => self.state = BodyState::Middle,
+                },
+                BodyState::Middle => {
+                    if byte == b'\n' {
+                        self.state = BodyState::LineStart;
+                    }
+                }
+                BodyState::Dot => match byte {
+                    b'\r' => {
+                        self.state = BodyState::DotCr;
+                        start = index + 1;
+                    }
+                    b'\n' => {
+                        self.state = BodyState::Done;
+                        return (index + 1, Some(BodyEvent::Done));
+                    }
+                    _ => self.state = BodyState::Middle,
+                },
+                BodyState::DotCr => {
+                    if byte == b'\n' {
+                        self.state = BodyState::Done;
+                        return (index + 1, Some(BodyEvent::Done));
+                    }
+
+                    // Not the terminator, i.e., the CR belongs to the (unstuffed) line.
+                    self.state = BodyState::Middle;
+                    return (index, Some(BodyEvent::Data(b"\r")));
+                }
+                BodyState::Head | BodyState::Done => unreachable!(),
+            }
+        }
+
+        if start < input.len() {
+            (input.len(), Some(BodyEvent::Data(&input[start..])))
+        } else {
+            (input.len(), None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse::response_retr, types::IntoOwned};
+
+    fn decode_chunked(
+        input: &[u8],
+        chunk_size: usize,
+    ) -> (
+        Response<SingleLine<'static>, SingleLine<'static>>,
+        Vec<u8>,
+        Vec<u8>,
+    ) {
+        let mut decoder = BodyDecoder::new();
+        let mut buffer = Vec::new();
+        let mut chunks = input.chunks(chunk_size);
+        let mut head = None;
+        let mut body = Vec::new();
+
+        while !decoder.is_done() {
+            let (consumed, event) = decoder.decode(&buffer).unwrap();
+
+            match event {
+                Some(BodyEvent::Head(response)) => head = Some(response.into_owned()),
+                Some(BodyEvent::Data(data)) => body.extend_from_slice(data),
+                Some(BodyEvent::Done) => {}
+                None => buffer.extend_from_slice(chunks.next().unwrap()),
+            }
+
+            buffer.drain(..consumed);
+        }
+
+        let mut rem = buffer;
+        for chunk in chunks {
+            rem.extend_from_slice(chunk);
+        }
+
+        (head.unwrap(), body, rem)
+    }
+
+    #[test]
+    fn test_body_decoder() {
+        let tests: &[&[u8]] = &[
+            b"+OK\r\n.\r\n",
+            b"+OK 120 octets\r\nSubject: Test\r\n\r\nHello!\r\n.\r\n",
+            b"+OK\r\n..\r\n...\r\n..foo\r\nfoo.\r\n.foo\r\n.\r\n",
+            b"+OK\r\nGr\xfc\xdfe\r\n.\r\n+OK next response\r\n",
+            b"-ERR no such message\r\n+OK next response\r\n",
+        ];
+
+        for test in tests {
+            let (rem, expected) = response_retr(test).unwrap();
+
+            for chunk_size in 1..=test.len() {
+                let (head, body, got_rem) = decode_chunked(test, chunk_size);
+
+                match &expected {
+                    Response::Ok(expected) => {
+                        assert_eq!(head, Response::Ok(expected.head.clone()));
+                        assert_eq!(body, expected.body_bytes());
+                    }
+                    Response::Err(expected) => {
+                        assert_eq!(head, Response::Err(expected.clone()));
+                        assert!(body.is_empty());
+                    }
+                }
+                assert_eq!(got_rem, rem);
+            }
+        }
+    }
+
+    #[test]
+    fn test_body_decoder_zero_copy() {
+        let mut decoder = BodyDecoder::new();
+
+        let input = b"+OK\r\nabc\r\n..def\r\n.\r\n";
+
+        let (consumed, event) = decoder.decode(input).unwrap();
+        assert_eq!(consumed, 5);
+        assert!(matches!(event, Some(BodyEvent::Head(Response::Ok(_)))));
+        let input = &input[consumed..];
+
+        let (consumed, event) = decoder.decode(input).unwrap();
+        assert_eq!(event, Some(BodyEvent::Data(b"abc\r\n")));
+        let input = &input[consumed..];
+
+        let (consumed, event) = decoder.decode(input).unwrap();
+        assert_eq!(event, Some(BodyEvent::Data(b".def\r\n")));
+        let input = &input[consumed..];
+
+        let (consumed, event) = decoder.decode(input).unwrap();
+        assert_eq!(event, Some(BodyEvent::Done));
+        assert_eq!(consumed, input.len());
+        assert!(decoder.is_done());
+    }
+}
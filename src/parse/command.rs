@@ -1,4 +1,4 @@
-use std::str::from_utf8;
+use std::{borrow::Cow, str::from_utf8};
 
 use nom::{
     branch::alt,
@@ -10,11 +10,14 @@ use nom::{
 };
 
 use crate::{
-    parse::{language, number},
-    types::command::{Command, Language},
+    parse::{language_tag, message_number, number},
+    types::{
+        command::{AuthData, Command, Mechanism},
+        language::Language,
+    },
 };
 
-pub(crate) fn user(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn user(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = tuple((
         tag_no_case("USER"),
         tag(" "),
@@ -23,10 +26,10 @@ pub(crate) fn user(input: &[u8]) -> IResult<&[u8], Command> {
 
     let (remaining, (_, _, name)) = parser(input)?;
 
-    Ok((remaining, Command::User(name.into())))
+    Ok((remaining, Command::User(Cow::Borrowed(name))))
 }
 
-pub(crate) fn pass(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn pass(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = tuple((
         tag_no_case("PASS"),
         tag(" "),
@@ -35,17 +38,17 @@ pub(crate) fn pass(input: &[u8]) -> IResult<&[u8], Command> {
 
     let (remaining, (_, _, pass)) = parser(input)?;
 
-    Ok((remaining, Command::Pass(pass.into())))
+    Ok((remaining, Command::Pass(Cow::Borrowed(pass))))
 }
 
-pub(crate) fn stat(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn stat(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Stat, tag_no_case("STAT"))(input)
 }
 
-pub(crate) fn list(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn list(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = tuple((
         tag_no_case("LIST"),
-        opt(map(tuple((tag(" "), number)), |(_, msg)| msg)),
+        opt(map(tuple((tag(" "), message_number)), |(_, msg)| msg)),
     ));
 
     let (remaining, (_, maybe_msg)) = parser(input)?;
@@ -59,35 +62,35 @@ pub(crate) fn list(input: &[u8]) -> IResult<&[u8], Command> {
     ))
 }
 
-pub(crate) fn retr(input: &[u8]) -> IResult<&[u8], Command> {
-    let mut parser = tuple((tag_no_case("RETR"), tag(" "), number));
+pub(crate) fn retr(input: &[u8]) -> IResult<&[u8], Command<'_>> {
+    let mut parser = tuple((tag_no_case("RETR"), tag(" "), message_number));
 
     let (remaining, (_, _, msg)) = parser(input)?;
 
     Ok((remaining, Command::Retr { msg }))
 }
 
-pub(crate) fn dele(input: &[u8]) -> IResult<&[u8], Command> {
-    let mut parser = tuple((tag_no_case("DELE"), tag(" "), number));
+pub(crate) fn dele(input: &[u8]) -> IResult<&[u8], Command<'_>> {
+    let mut parser = tuple((tag_no_case("DELE"), tag(" "), message_number));
 
     let (remaining, (_, _, msg)) = parser(input)?;
 
     Ok((remaining, Command::Dele { msg }))
 }
 
-pub(crate) fn noop(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn noop(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Noop, tag_no_case("NOOP"))(input)
 }
 
-pub(crate) fn rset(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn rset(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Rset, tag_no_case("RSET"))(input)
 }
 
-pub(crate) fn quit(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn quit(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Quit, tag_no_case("QUIT"))(input)
 }
 
-pub(crate) fn apop(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn apop(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = tuple((
         tag_no_case("APOP"),
         tag(" "),
@@ -101,22 +104,28 @@ pub(crate) fn apop(input: &[u8]) -> IResult<&[u8], Command> {
     Ok((
         remaining,
         Command::Apop {
-            name: name.into(),
-            digest: digest.into(),
+            name: Cow::Borrowed(name),
+            digest: Cow::Borrowed(digest),
         },
     ))
 }
 
-pub(crate) fn top(input: &[u8]) -> IResult<&[u8], Command> {
-    let mut parser = tuple((tag_no_case("TOP"), tag(" "), number, tag(" "), number));
+pub(crate) fn top(input: &[u8]) -> IResult<&[u8], Command<'_>> {
+    let mut parser = tuple((
+        tag_no_case("TOP"),
+        tag(" "),
+        message_number,
+        tag(" "),
+        number,
+    ));
 
     let (remaining, (_, _, msg, _, n)) = parser(input)?;
 
     Ok((remaining, Command::Top { msg, n }))
 }
 
-pub(crate) fn uidl(input: &[u8]) -> IResult<&[u8], Command> {
-    let mut parser = preceded(tag_no_case("UIDL"), opt(preceded(tag(" "), number)));
+pub(crate) fn uidl(input: &[u8]) -> IResult<&[u8], Command<'_>> {
+    let mut parser = preceded(tag_no_case("UIDL"), opt(preceded(tag(" "), message_number)));
 
     let (remaining, maybe_msg) = parser(input)?;
 
@@ -129,15 +138,15 @@ pub(crate) fn uidl(input: &[u8]) -> IResult<&[u8], Command> {
     ))
 }
 
-pub(crate) fn capa(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn capa(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Capa, tag_no_case("CAPA"))(input)
 }
 
-pub(crate) fn stls(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn stls(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Stls, tag_no_case("STLS"))(input)
 }
 
-pub(crate) fn auth(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn auth(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = alt((
         map(
             tuple((
@@ -153,8 +162,8 @@ pub(crate) fn auth(input: &[u8]) -> IResult<&[u8], Command> {
                 )),
             )),
             |(_, _, mechanism, initial_response)| Command::Auth {
-                mechanism: mechanism.to_owned(),
-                initial_response: initial_response.map(|i| i.to_owned()),
+                mechanism,
+                initial_response: initial_response.map(Cow::Borrowed),
             },
         ),
         map(tag_no_case("AUTH"), |_| Command::AuthAll),
@@ -165,11 +174,11 @@ pub(crate) fn auth(input: &[u8]) -> IResult<&[u8], Command> {
     Ok((remaining, cmd))
 }
 
-pub(crate) fn utf8(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn utf8(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     value(Command::Utf8, tag_no_case("UTF8"))(input)
 }
 
-pub(crate) fn lang(input: &[u8]) -> IResult<&[u8], Command> {
+pub(crate) fn lang(input: &[u8]) -> IResult<&[u8], Command<'_>> {
     let mut parser = preceded(tag_no_case("LANG"), opt(preceded(tag(" "), lang_or_wild)));
 
     let (remaining, maybe_lang) = parser(input)?;
@@ -183,17 +192,33 @@ pub(crate) fn lang(input: &[u8]) -> IResult<&[u8], Command> {
     ))
 }
 
+/// Line sent by the client during the AUTH exchange.
+///
+/// Note: Do not consume CRLF, because it is done in higher-level parser
+pub(crate) fn auth_data(input: &[u8]) -> IResult<&[u8], AuthData<'_>> {
+    alt((
+        value(AuthData::Cancel, tag("*")),
+        map(base64, |response| {
+            AuthData::Response(Cow::Borrowed(response))
+        }),
+    ))(input)
+}
+
 // -------------------------------------------------------------------------------------------------
 
-fn auth_type(input: &[u8]) -> IResult<&[u8], &str> {
-    map_res(take_while1(is_auth_char), std::str::from_utf8)(input)
+/// auth-type = 1*(ALPHA / DIGIT / "-" / "_")
+pub(crate) fn auth_type(input: &[u8]) -> IResult<&[u8], Mechanism> {
+    map(
+        map_res(take_while1(is_auth_char), std::str::from_utf8),
+        Mechanism::from_valid,
+    )(input)
 }
 
-fn is_auth_char(i: u8) -> bool {
+pub(crate) fn is_auth_char(i: u8) -> bool {
     is_alpha(i) || is_digit(i) || i == b'-' || i == b'_'
 }
 
-fn base64(input: &[u8]) -> IResult<&[u8], &str> {
+pub(crate) fn base64(input: &[u8]) -> IResult<&[u8], &str> {
     let mut parser = map_res(
         recognize(tuple((
             take_while(is_base64_char),
@@ -212,16 +237,16 @@ fn is_base64_char(byte: u8) -> bool {
 }
 
 fn is_alpha(byte: u8) -> bool {
-    matches!(byte, b'a'..=b'z' | b'A'..=b'Z')
+    byte.is_ascii_alphabetic()
 }
 
 fn is_digit(byte: u8) -> bool {
-    matches!(byte, b'0'..=b'9')
+    byte.is_ascii_digit()
 }
 
 fn lang_or_wild(input: &[u8]) -> IResult<&[u8], Language> {
     alt((
         value(Language::Wild, tag("*")),
-        map(language, |lang| Language::Lang(lang.to_string())),
+        map(language_tag, Language::Lang),
     ))(input)
 }
@@ -1,70 +1,99 @@
-use std::str::from_utf8;
+use std::{borrow::Cow, str::from_utf8};
 
-use abnf_core::streaming::{is_ALPHA, is_VCHAR, SP};
+use abnf_core::streaming::is_VCHAR;
 use nom::{
     branch::alt,
-    bytes::streaming::{tag, tag_no_case, take_while, take_while1, take_while_m_n},
-    character::{
-        is_alphanumeric,
-        streaming::{digit1, line_ending},
-    },
-    combinator::{map_res, opt, recognize},
-    multi::many0,
+    bytes::streaming::{take_while, take_while1},
+    character::streaming::digit1,
+    combinator::{map, map_opt, map_res, opt},
+    error::ErrorKind,
     sequence::{preceded, terminated, tuple},
-    IResult,
+    IResult, Needed,
 };
 
 use crate::{
+    error::{DecodeError, DecodeResult},
     parse::{command::*, response::*},
     types::{
-        command::Command,
+        command::{AuthData, Command},
+        language::LanguageTag,
         response::{
-            Capability, DropListing, Greeting, LanguageListing, MultiLine, Response, ScanListing,
-            SingleLine, UniqueIdListing,
+            AnyResponse, AuthResponse, Capability, DropListing, Greeting, LanguageListing,
+            MultiLine, Response, ResponseCode, ScanListing, SingleLine, UniqueIdListing,
         },
+        MessageNumber,
     },
 };
 
+mod body;
 mod command;
+mod multi_line;
+mod options;
 mod response;
 
+pub use body::{BodyDecoder, BodyEvent};
+pub(crate) use command::is_auth_char;
+pub use multi_line::{MultiLineIter, ParseLine};
+pub use options::ParseOptions;
+
+/// Maximum length of a response line (including CRLF), see RFC 1939 and RFC 2449.
+const MAX_RESPONSE_LINE: usize = 512;
+
+/// Maximum length of a command line (including CRLF), see RFC 2449.
+const MAX_COMMAND_LINE: usize = 255;
+
 /// Parses the server greeting.
-pub fn greeting(input: &[u8]) -> IResult<&[u8], Greeting> {
+///
+/// Note: The greeting may be up to 512 octets long (including CRLF), see RFC 1939.
+pub fn greeting(input: &[u8]) -> DecodeResult<'_, Greeting<'_>> {
+    ParseOptions::default().greeting(input)
+}
+
+fn greeting_inner<'a>(input: &'a [u8], options: &ParseOptions) -> IResult<&'a [u8], Greeting<'a>> {
     // greeting = "+OK" [resp-code] *gchar [timestamp] *gchar CRLF
     //
     // Corrections:
     // * [resp-code] -> [SP resp-code]
-    //
-    // TODO: 512 octets maximum (?)
     let mut parser = tuple((
-        tag_no_case("+OK"),
-        opt(preceded(SP, resp_code)),
+        |i| options.status("+OK", i),
+        opt(preceded(|i| options.status_sp(i), resp_code)),
         opt(preceded(
-            SP,
+            |i| options.status_sp(i),
             tuple((
                 map_res(take_while(is_gchar), from_utf8),
                 opt(timestamp),
                 map_res(take_while(is_gchar), from_utf8),
             )),
         )),
-        line_ending,
+        |i| options.line_ending(i),
     ));
 
     let (rem, (_, maybe_code, maybe_body, _)) = parser(input)?;
 
-    let code = maybe_code
-        .unwrap_or_default()
-        .into_iter()
-        .map(|lvl| lvl.to_owned())
-        .collect();
+    let code = maybe_code.map(|levels| ResponseCode::from_levels(&levels));
 
     let res = match maybe_body {
         Some((comment1, maybe_timestamp, comment2)) => {
-            let timestamp = maybe_timestamp.map(ToOwned::to_owned);
+            let timestamp = maybe_timestamp.map(Cow::Borrowed);
 
-            let comment = match timestamp.as_ref() {
-                Some(_) => format!("{}<>{}", comment1, comment2),
-                None => format!("{}{}", comment1, comment2),
+            // A comment, which would be parsed as a response code, is escaped with a leading space
+            // by `Greeting::serialize`.
+            let comment1 = match comment1.strip_prefix(' ') {
+                Some(rest) if code.is_none() && rest.trim_start_matches(' ').starts_with('[') => {
+                    rest
+                }
+                _ => comment1,
+            };
+
+            // Without a timestamp, the whole comment is matched by `comment1`. A timestamp at the
+            // end is appended by `Greeting::serialize` anyway, i.e., it needs no placeholder.
+            let comment = match (timestamp.as_ref(), comment1.strip_suffix(' ')) {
+                (Some(_), _) if comment1.is_empty() && comment2.is_empty() => Cow::Borrowed(""),
+                (Some(_), Some(head)) if !head.is_empty() && comment2.is_empty() => {
+                    Cow::Borrowed(head)
+                }
+                (Some(_), _) => Cow::Owned(format!("{}<>{}", comment1, comment2)),
+                (None, _) => Cow::Borrowed(comment1),
             };
 
             Greeting {
@@ -86,127 +115,599 @@ pub fn greeting(input: &[u8]) -> IResult<&[u8], Greeting> {
 /// Parses any command.
 ///
 /// See the [Command](crate::types::Command) enum for supported commands.
-pub fn command(input: &[u8]) -> IResult<&[u8], Command> {
-    terminated(
-        alt((
-            user, pass, apop, stls, // AUTHORIZATION
-            capa, quit, // AUTHORIZATION + TRANSACTION
-            stat, list, retr, dele, noop, rset, top, uidl, // TRANSACTION
-            auth, utf8, lang, // not sorted yet
-        )),
-        line_ending,
-    )(input)
+///
+/// Note: Commands may be up to 255 octets long (including CRLF), see RFC 2449.
+pub fn command(input: &[u8]) -> DecodeResult<'_, Command<'_>> {
+    ParseOptions::default().command(input)
+}
+
+/// Parses the response to `command`.
+///
+/// This is a convenience function, which selects the matching `response_*` parser.
+///
+/// Note: The response to [Auth](crate::types::Command::Auth) is parsed using [response_auth],
+/// i.e., it may be a challenge, which must be answered by the client.
+pub fn response<'a>(command: &Command<'_>, input: &'a [u8]) -> DecodeResult<'a, AnyResponse<'a>> {
+    ParseOptions::default().response(command, input)
 }
 
 /// Parses the response to the [User](crate::types::Command::User) command.
-pub fn response_user(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_user(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_user(input)
 }
 
 /// Parses the response to the [Pass](crate::types::Command::Pass) command.
-pub fn response_pass(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_pass(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_pass(input)
 }
 
 /// Parses the response to the [Stat](crate::types::Command::Stat) command.
-pub fn response_stat(input: &[u8]) -> IResult<&[u8], Response<DropListing, SingleLine>> {
-    single_line(input, drop_listing, true)
+pub fn response_stat(input: &[u8]) -> DecodeResult<'_, Response<DropListing<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_stat(input)
 }
 
 /// Parses the response to the [ListAll](crate::types::Command::ListAll) command, i.e. LIST without a parameter.
 pub fn response_list_all(
     input: &[u8],
-) -> IResult<&[u8], Response<MultiLine<ScanListing>, SingleLine>> {
-    multi_line(input, scan_listing)
+) -> DecodeResult<'_, Response<MultiLine<'_, ScanListing<'_>>, SingleLine<'_>>> {
+    ParseOptions::default().response_list_all(input)
+}
+
+/// Parses the status line of the response to the [ListAll](crate::types::Command::ListAll) command
+/// and returns a lazy iterator over the listings.
+///
+/// On success, the remaining input starts after the status line, i.e., the body is consumed by the
+/// iterator. See [MultiLineIter] for details.
+pub fn response_list_all_iter(
+    input: &[u8],
+) -> DecodeResult<'_, Response<(SingleLine<'_>, MultiLineIter<'_, ScanListing<'_>>), SingleLine<'_>>>
+{
+    ParseOptions::default().response_list_all_iter(input)
 }
 
 /// Parses the response to the [List](crate::types::Command::List) command, i.e. LIST with a parameter.
-pub fn response_list(input: &[u8]) -> IResult<&[u8], Response<ScanListing, SingleLine>> {
-    single_line(input, scan_listing, true)
+pub fn response_list(input: &[u8]) -> DecodeResult<'_, Response<ScanListing<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_list(input)
 }
 
 /// Parses the response to the [Retr](crate::types::Command::Retr) command.
-pub fn response_retr(input: &[u8]) -> IResult<&[u8], Response<MultiLine<String>, SingleLine>> {
-    multi_line(input, dot_stuffed)
+///
+/// Lines of the message are returned as raw octets (without CRLF) with byte-stuffing removed.
+/// See [MultiLine::body_bytes](crate::types::MultiLine::body_bytes) and friends for convenient views.
+pub fn response_retr(
+    input: &[u8],
+) -> DecodeResult<'_, Response<MultiLine<'_, Cow<'_, [u8]>>, SingleLine<'_>>> {
+    ParseOptions::default().response_retr(input)
 }
 
 /// Parses the response to the [Dele](crate::types::Command::Dele) command.
-pub fn response_dele(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_dele(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_dele(input)
 }
 
 /// Parses the response to the [Noop](crate::types::Command::Noop) command.
-pub fn response_noop(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_noop(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_noop(input)
 }
 
 /// Parses the response to the [Rset](crate::types::Command::Rset) command.
-pub fn response_rset(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_rset(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_rset(input)
 }
 
 /// Parses the response to the [Quit](crate::types::Command::Quit) command.
-pub fn response_quit(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_quit(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_quit(input)
 }
 
 /// Parses the response to the [Apop](crate::types::Command::Apop) command.
-pub fn response_apop(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_apop(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_apop(input)
 }
 
 /// Parses the response to the [Top](crate::types::Command::Top) command.
-pub fn response_top(input: &[u8]) -> IResult<&[u8], Response<MultiLine<String>, SingleLine>> {
-    multi_line(input, dot_stuffed)
+///
+/// Lines of the message are returned as raw octets (without CRLF) with byte-stuffing removed.
+/// See [MultiLine::body_bytes](crate::types::MultiLine::body_bytes) and friends for convenient views.
+pub fn response_top(
+    input: &[u8],
+) -> DecodeResult<'_, Response<MultiLine<'_, Cow<'_, [u8]>>, SingleLine<'_>>> {
+    ParseOptions::default().response_top(input)
 }
 
 /// Parses the response to the [UidlAll](crate::types::Command::UidlAll) command, i.e. UIDL when used without a parameter.
 pub fn response_uidl_all(
     input: &[u8],
-) -> IResult<&[u8], Response<MultiLine<UniqueIdListing>, SingleLine>> {
-    multi_line(input, unique_id_listing)
+) -> DecodeResult<'_, Response<MultiLine<'_, UniqueIdListing<'_>>, SingleLine<'_>>> {
+    ParseOptions::default().response_uidl_all(input)
+}
+
+/// Parses the status line of the response to the [UidlAll](crate::types::Command::UidlAll) command
+/// and returns a lazy iterator over the listings.
+///
+/// See [response_list_all_iter].
+pub fn response_uidl_all_iter(
+    input: &[u8],
+) -> DecodeResult<
+    '_,
+    Response<(SingleLine<'_>, MultiLineIter<'_, UniqueIdListing<'_>>), SingleLine<'_>>,
+> {
+    ParseOptions::default().response_uidl_all_iter(input)
 }
 
 /// Parses the response to the [Uidl](crate::types::Command::Uidl) command, i.e. UIDL when used with a parameter.
-pub fn response_uidl(input: &[u8]) -> IResult<&[u8], Response<UniqueIdListing, SingleLine>> {
-    single_line(input, unique_id_listing, true)
+pub fn response_uidl(
+    input: &[u8],
+) -> DecodeResult<'_, Response<UniqueIdListing<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_uidl(input)
 }
 
 /// Parses the response to the [Capa](crate::types::Command::Capa) command.
-pub fn response_capa(input: &[u8]) -> IResult<&[u8], Response<MultiLine<Capability>, SingleLine>> {
-    // capa-resp = single-line *capability "." CRLF
-    multi_line(input, capability)
+pub fn response_capa(
+    input: &[u8],
+) -> DecodeResult<'_, Response<MultiLine<'_, Capability<'_>>, SingleLine<'_>>> {
+    ParseOptions::default().response_capa(input)
 }
 
 /// Parses the response to the [Stls](crate::types::Command::Stls) command.
-pub fn response_stls(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_stls(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_stls(input)
 }
 
 /// Parses the response to the [AuthAll](crate::types::Command::AuthAll) command, i.e. AUTH when used without a parameter.
 ///
 /// Note: This command appears to be non-standard. However, MUAs use it and popular POP3 servers understand it.
-pub fn response_auth_all(input: &[u8]) -> IResult<&[u8], Response<MultiLine<String>, SingleLine>> {
-    multi_line(input, dot_stuffed)
+pub fn response_auth_all(
+    input: &[u8],
+) -> DecodeResult<'_, Response<MultiLine<'_, Cow<'_, str>>, SingleLine<'_>>> {
+    ParseOptions::default().response_auth_all(input)
 }
 
-// TODO: response_auth
+/// Parses a response of the server during the exchange initiated by the [Auth](crate::types::Command::Auth) command.
+///
+/// The server either sends a (base64 encoded) challenge, which must be answered by the client
+/// (see [auth_data]), or finishes the exchange with "+OK" or "-ERR".
+pub fn response_auth(input: &[u8]) -> DecodeResult<'_, AuthResponse<'_>> {
+    ParseOptions::default().response_auth(input)
+}
+
+/// Parses a line sent by the client in response to a challenge during the AUTH exchange.
+///
+/// See [response_auth].
+pub fn auth_data(input: &[u8]) -> DecodeResult<'_, AuthData<'_>> {
+    ParseOptions::default().auth_data(input)
+}
 
 /// Parses the response to the [Utf8](crate::types::Command::Utf8) command.
-pub fn response_utf8(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_utf8(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_utf8(input)
 }
 
 /// Parses the response to the [LangAll](crate::types::Command::LangAll) command, i.e. LANG when used without a parameter.
 pub fn response_lang_all(
     input: &[u8],
-) -> IResult<&[u8], Response<MultiLine<LanguageListing>, SingleLine>> {
-    multi_line(input, language_listing)
+) -> DecodeResult<'_, Response<MultiLine<'_, LanguageListing<'_>>, SingleLine<'_>>> {
+    ParseOptions::default().response_lang_all(input)
 }
 
 /// Parses the response to the [Lang](crate::types::Command::Lang) command, i.e. LANG when used with a parameter.
-pub fn response_lang(input: &[u8]) -> IResult<&[u8], Response<SingleLine, SingleLine>> {
-    single_line(input, head, false)
+pub fn response_lang(input: &[u8]) -> DecodeResult<'_, Response<SingleLine<'_>, SingleLine<'_>>> {
+    ParseOptions::default().response_lang(input)
+}
+
+impl ParseOptions {
+    /// Same as [greeting], but with these options.
+    pub fn greeting<'a>(&self, input: &'a [u8]) -> DecodeResult<'a, Greeting<'a>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            greeting_inner(i, self)
+        })
+    }
+
+    /// Same as [command], but with these options.
+    pub fn command<'a>(&self, input: &'a [u8]) -> DecodeResult<'a, Command<'a>> {
+        let parser = terminated(
+            alt((
+                user, pass, apop, stls, // AUTHORIZATION
+                capa, quit, // AUTHORIZATION + TRANSACTION
+                stat, list, retr, dele, noop, rset, top, uidl, // TRANSACTION
+                auth, utf8, lang, // not sorted yet
+            )),
+            |i| self.line_ending(i),
+        );
+
+        finish(input, self.limit(MAX_COMMAND_LINE), parser)
+    }
+
+    /// Same as [response], but with these options.
+    pub fn response<'a>(
+        &self,
+        command: &Command<'_>,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, AnyResponse<'a>> {
+        match command {
+            Command::User(_) => self
+                .response_user(input)
+                .map(|(rem, r)| (rem, AnyResponse::User(r))),
+            Command::Pass(_) => self
+                .response_pass(input)
+                .map(|(rem, r)| (rem, AnyResponse::Pass(r))),
+            Command::Stat => self
+                .response_stat(input)
+                .map(|(rem, r)| (rem, AnyResponse::Stat(r))),
+            Command::ListAll => self
+                .response_list_all(input)
+                .map(|(rem, r)| (rem, AnyResponse::ListAll(r))),
+            Command::List { .. } => self
+                .response_list(input)
+                .map(|(rem, r)| (rem, AnyResponse::List(r))),
+            Command::Retr { .. } => self
+                .response_retr(input)
+                .map(|(rem, r)| (rem, AnyResponse::Retr(r))),
+            Command::Dele { .. } => self
+                .response_dele(input)
+                .map(|(rem, r)| (rem, AnyResponse::Dele(r))),
+            Command::Noop => self
+                .response_noop(input)
+                .map(|(rem, r)| (rem, AnyResponse::Noop(r))),
+            Command::Rset => self
+                .response_rset(input)
+                .map(|(rem, r)| (rem, AnyResponse::Rset(r))),
+            Command::Quit => self
+                .response_quit(input)
+                .map(|(rem, r)| (rem, AnyResponse::Quit(r))),
+            Command::Apop { .. } => self
+                .response_apop(input)
+                .map(|(rem, r)| (rem, AnyResponse::Apop(r))),
+            Command::Top { .. } => self
+                .response_top(input)
+                .map(|(rem, r)| (rem, AnyResponse::Top(r))),
+            Command::UidlAll => self
+                .response_uidl_all(input)
+                .map(|(rem, r)| (rem, AnyResponse::UidlAll(r))),
+            Command::Uidl { .. } => self
+                .response_uidl(input)
+                .map(|(rem, r)| (rem, AnyResponse::Uidl(r))),
+            Command::Capa => self
+                .response_capa(input)
+                .map(|(rem, r)| (rem, AnyResponse::Capa(r))),
+            Command::Stls => self
+                .response_stls(input)
+                .map(|(rem, r)| (rem, AnyResponse::Stls(r))),
+            Command::AuthAll => self
+                .response_auth_all(input)
+                .map(|(rem, r)| (rem, AnyResponse::AuthAll(r))),
+            Command::Auth { .. } => self
+                .response_auth(input)
+                .map(|(rem, r)| (rem, AnyResponse::Auth(r))),
+            Command::Utf8 => self
+                .response_utf8(input)
+                .map(|(rem, r)| (rem, AnyResponse::Utf8(r))),
+            Command::LangAll => self
+                .response_lang_all(input)
+                .map(|(rem, r)| (rem, AnyResponse::LangAll(r))),
+            Command::Lang { .. } => self
+                .response_lang(input)
+                .map(|(rem, r)| (rem, AnyResponse::Lang(r))),
+        }
+    }
+
+    /// Same as [response_user], but with these options.
+    pub fn response_user<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_pass], but with these options.
+    pub fn response_pass<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_stat], but with these options.
+    pub fn response_stat<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<DropListing<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| drop_listing(i, self), true)
+        })
+    }
+
+    /// Same as [response_list_all], but with these options.
+    pub fn response_list_all<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, ScanListing<'a>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| scan_listing(i, self))
+        })
+    }
+
+    /// Same as [response_list_all_iter], but with these options.
+    pub fn response_list_all_iter<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<
+        'a,
+        Response<(SingleLine<'a>, MultiLineIter<'a, ScanListing<'a>>), SingleLine<'a>>,
+    > {
+        multi_line_iter(input, self)
+    }
+
+    /// Same as [response_list], but with these options.
+    pub fn response_list<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<ScanListing<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| scan_listing(i, self), true)
+        })
+    }
+
+    /// Same as [response_retr], but with these options.
+    pub fn response_retr<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, Cow<'a, [u8]>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| dot_stuffed(i, self))
+        })
+    }
+
+    /// Same as [response_dele], but with these options.
+    pub fn response_dele<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_noop], but with these options.
+    pub fn response_noop<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_rset], but with these options.
+    pub fn response_rset<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_quit], but with these options.
+    pub fn response_quit<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_apop], but with these options.
+    pub fn response_apop<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_top], but with these options.
+    pub fn response_top<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, Cow<'a, [u8]>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| dot_stuffed(i, self))
+        })
+    }
+
+    /// Same as [response_uidl_all], but with these options.
+    pub fn response_uidl_all<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, UniqueIdListing<'a>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| unique_id_listing(i, self))
+        })
+    }
+
+    /// Same as [response_uidl_all_iter], but with these options.
+    pub fn response_uidl_all_iter<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<
+        'a,
+        Response<(SingleLine<'a>, MultiLineIter<'a, UniqueIdListing<'a>>), SingleLine<'a>>,
+    > {
+        multi_line_iter(input, self)
+    }
+
+    /// Same as [response_uidl], but with these options.
+    pub fn response_uidl<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<UniqueIdListing<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| unique_id_listing(i, self), true)
+        })
+    }
+
+    /// Same as [response_capa], but with these options.
+    pub fn response_capa<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, Capability<'a>>, SingleLine<'a>>> {
+        // capa-resp = single-line *capability "." CRLF
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| capability(i, self))
+        })
+    }
+
+    /// Same as [response_stls], but with these options.
+    pub fn response_stls<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_auth_all], but with these options.
+    pub fn response_auth_all<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, Cow<'a, str>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| dot_stuffed_utf8(i, self))
+        })
+    }
+
+    /// Same as [response_auth], but with these options.
+    pub fn response_auth<'a>(&self, input: &'a [u8]) -> DecodeResult<'a, AuthResponse<'a>> {
+        let parser = alt((
+            map(
+                |input| single_line(input, self, |i| head(i, self), false),
+                AuthResponse::Done,
+            ),
+            map(
+                terminated(continue_req, |i| self.line_ending(i)),
+                |challenge| AuthResponse::Continue(Cow::Borrowed(challenge)),
+            ),
+        ));
+
+        finish(input, None, parser)
+    }
+
+    /// Same as [auth_data], but with these options.
+    pub fn auth_data<'a>(&self, input: &'a [u8]) -> DecodeResult<'a, AuthData<'a>> {
+        finish(
+            input,
+            None,
+            terminated(command::auth_data, |i| self.line_ending(i)),
+        )
+    }
+
+    /// Same as [response_utf8], but with these options.
+    pub fn response_utf8<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+
+    /// Same as [response_lang_all], but with these options.
+    pub fn response_lang_all<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<MultiLine<'a, LanguageListing<'a>>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            multi_line(i, self, |i| language_listing(i, self))
+        })
+    }
+
+    /// Same as [response_lang], but with these options.
+    pub fn response_lang<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> DecodeResult<'a, Response<SingleLine<'a>, SingleLine<'a>>> {
+        finish(input, self.limit(MAX_RESPONSE_LINE), |i| {
+            single_line(i, self, |i| head(i, self), false)
+        })
+    }
+}
+
+/// Parses the status line of a multi-line response and returns an iterator over the body.
+fn multi_line_iter<'a, T>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> DecodeResult<'a, Response<(SingleLine<'a>, MultiLineIter<'a, T>), SingleLine<'a>>>
+where
+    T: ParseLine<'a> + std::fmt::Debug + Clone + PartialEq + Eq,
+{
+    let (rem, status) = finish(input, options.limit(MAX_RESPONSE_LINE), |i| {
+        single_line(i, options, |i| head(i, options), false)
+    })?;
+
+    let response = match status {
+        Response::Ok(head) => Response::Ok((
+            head,
+            MultiLineIter::resume(rem, input.len() - rem.len()).with_options(*options),
+        )),
+        Response::Err(head) => Response::Err(head),
+    };
+
+    Ok((rem, response))
+}
+
+/// Runs `parser` and converts the result into a [DecodeResult].
+///
+/// When `limit` is given, the first line of `input` must not exceed `limit` octets.
+fn finish<'a, T, F>(input: &'a [u8], limit: Option<usize>, mut parser: F) -> DecodeResult<'a, T>
+where
+    F: FnMut(&'a [u8]) -> IResult<&'a [u8], T>,
+{
+    if let Some(limit) = limit {
+        let length = match input.iter().position(|byte| *byte == b'\n') {
+            Some(position) => position + 1,
+            None => input.len(),
+        };
+
+        if length > limit {
+            return Err(DecodeError::LineTooLong { limit });
+        }
+    }
+
+    match parser(input) {
+        Ok(result) => Ok(result),
+        Err(nom::Err::Incomplete(needed)) => Err(DecodeError::Incomplete {
+            needed: match needed {
+                Needed::Size(size) => Some(size),
+                Needed::Unknown => None,
+            },
+        }),
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => Err(DecodeError::Failed {
+            offset: input.len() - error.input.len(),
+            expected: expected(error.code),
+        }),
+    }
+}
+
+/// Describes what a failed nom parser expected.
+fn expected(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Tag => "keyword",
+        ErrorKind::Digit => "number",
+        ErrorKind::CrLf => "line ending",
+        ErrorKind::Char => "character",
+        ErrorKind::MapRes | ErrorKind::MapOpt => "valid value",
+        ErrorKind::IsNot | ErrorKind::TakeWhile1 | ErrorKind::TakeWhileMN => "text",
+        ErrorKind::Alt => "one of multiple alternatives",
+        _ => "valid input",
+    }
 }
 
 // -------------------------------------------------------------------------------------------------
@@ -215,20 +716,28 @@ pub(crate) fn number(input: &[u8]) -> IResult<&[u8], u32> {
     map_res(map_res(digit1, from_utf8), str::parse::<u32>)(input)
 }
 
+/// Size in octets, which may exceed 4 GiB, e.g., for a maildrop.
+pub(crate) fn size(input: &[u8]) -> IResult<&[u8], u64> {
+    map_res(map_res(digit1, from_utf8), str::parse::<u64>)(input)
+}
+
+/// Message numbers start at 1, i.e., "0" is rejected (RFC 1939).
+pub(crate) fn message_number(input: &[u8]) -> IResult<&[u8], MessageNumber> {
+    map_opt(number, MessageNumber::new)(input)
+}
+
 // -------------------------------------------------------------------------------------------------
 
-/// language-range = (1*8ALPHA *("-" 1*8alphanum)) / "*"
-///
-/// alphanum       = ALPHA / DIGIT
+/// Language-Tag = langtag / privateuse / grandfathered
 ///
-/// Note: don't use wildcard here, because it is only useful in command?
-pub(crate) fn language(input: &[u8]) -> IResult<&[u8], &str> {
+/// Note: The syntax is checked by [LanguageTag::from_str](std::str::FromStr::from_str).
+pub(crate) fn language_tag(input: &[u8]) -> IResult<&[u8], LanguageTag> {
     map_res(
-        recognize(tuple((
-            take_while_m_n(1, 8, is_ALPHA),
-            many0(tuple((tag(b"-"), take_while_m_n(1, 8, is_alphanumeric)))),
-        ))),
-        from_utf8,
+        map_res(
+            take_while1(|byte: u8| byte.is_ascii_alphanumeric() || byte == b'-'),
+            from_utf8,
+        ),
+        str::parse,
     )(input)
 }
 
@@ -250,6 +759,7 @@ pub(crate) fn param(input: &[u8]) -> IResult<&[u8], &str> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::{ExpirePolicy, IntoOwned, Mechanism, MessageNumber};
 
     #[test]
     fn test_greeting() {
@@ -257,7 +767,7 @@ mod test {
             (
                 b"+OK\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                     timestamp: None,
                 },
@@ -265,7 +775,7 @@ mod test {
             (
                 b"+OK \r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                     timestamp: None,
                 },
@@ -273,7 +783,7 @@ mod test {
             (
                 b"+OK A\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "A".into(),
                     timestamp: None,
                 },
@@ -281,7 +791,7 @@ mod test {
             (
                 b"+OK Z\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Z".into(),
                     timestamp: None,
                 },
@@ -289,7 +799,7 @@ mod test {
             (
                 b"+ok Hello World!\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Hello World!".into(),
                     timestamp: None,
                 },
@@ -297,7 +807,7 @@ mod test {
             (
                 b"+ok Hello <123> World!\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Hello <> World!".into(),
                     timestamp: Some("123".into()),
                 },
@@ -305,7 +815,7 @@ mod test {
             (
                 b"+ok [a] Hello World!\r\n",
                 Greeting {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "Hello World!".into(),
                     timestamp: None,
                 },
@@ -313,7 +823,7 @@ mod test {
             (
                 b"+ok [a] Hello <123> World!\r\n",
                 Greeting {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "Hello <> World!".into(),
                     timestamp: Some("123".into()),
                 },
@@ -560,6 +1070,115 @@ i-default Default language
         );
     }
 
+    #[test]
+    fn test_auth() {
+        // Example from RFC 5034
+        let tests: &[(&[u8], AuthResponse)] = &[
+            (b"+ \r\n", AuthResponse::Continue("".into())),
+            (
+                b"+ PDE4OTYuNjk3MTcwOTUyQHBvc3RvZmZpY2UucmVzdG9uLm1jaS5uZXQ+\r\n",
+                AuthResponse::Continue(
+                    "PDE4OTYuNjk3MTcwOTUyQHBvc3RvZmZpY2UucmVzdG9uLm1jaS5uZXQ+".into(),
+                ),
+            ),
+            (
+                b"+OK Maildrop locked and ready\r\n",
+                AuthResponse::Done(Response::Ok(SingleLine {
+                    code: None,
+                    comment: "Maildrop locked and ready".into(),
+                })),
+            ),
+            (
+                b"-ERR [AUTH] Authentication failed\r\n",
+                AuthResponse::Done(Response::Err(SingleLine {
+                    code: Some(ResponseCode::Auth),
+                    comment: "Authentication failed".into(),
+                })),
+            ),
+        ];
+
+        for (test, expected) in tests {
+            let (rem, got) = response_auth(test).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(*expected, got);
+            assert_eq!(got.serialize(), *test);
+        }
+
+        let tests: &[(&[u8], AuthData)] = &[
+            (b"\r\n", AuthData::Response("".into())),
+            (
+                b"dGVzdAB0ZXN0AHRlc3Q=\r\n",
+                AuthData::Response("dGVzdAB0ZXN0AHRlc3Q=".into()),
+            ),
+            (b"*\r\n", AuthData::Cancel),
+        ];
+
+        for (test, expected) in tests {
+            let (rem, got) = auth_data(test).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(*expected, got);
+            assert_eq!(got.serialize(), *test);
+        }
+
+        assert!(auth_data(b"not base64!\r\n").is_err());
+
+        // SASL responses (e.g., GSSAPI tokens) are not limited like commands (RFC 5034).
+        let mut long = vec![b'A'; 2048];
+        long.extend_from_slice(b"\r\n");
+        assert_eq!(
+            ParseOptions::strict().auth_data(&long).unwrap().1,
+            AuthData::Response(std::str::from_utf8(&long[..2048]).unwrap().into())
+        );
+    }
+
+    #[test]
+    fn test_example_session_auth() {
+        let client = b"\
+AUTH PLAIN
+dGVzdAB0ZXN0AHRlc3Q=
+AUTH CRAM-MD5
+*
+";
+
+        let server = b"\
++OK POP3 server ready
++ \r
++OK Maildrop locked and ready
++ PDE4OTYuNjk3MTcwOTUyQHBvc3RvZmZpY2UucmVzdG9uLm1jaS5uZXQ+
+-ERR [AUTH] Authentication cancelled
+";
+
+        let (mut rem_server, _) = greeting(server.as_ref()).unwrap();
+        let mut rem_client = client.as_ref();
+        let mut results = vec![];
+
+        while !rem_client.is_empty() {
+            let (rem, cmd) = command(rem_client).unwrap();
+            assert!(matches!(cmd, Command::Auth { .. }));
+            rem_client = rem;
+
+            loop {
+                let (rem, resp) = response_auth(rem_server).unwrap();
+                rem_server = rem;
+
+                match resp {
+                    AuthResponse::Continue(_) => {
+                        let (rem, _) = auth_data(rem_client).unwrap();
+                        rem_client = rem;
+                    }
+                    AuthResponse::Done(resp) => {
+                        results.push(resp);
+                        break;
+                    }
+                }
+            }
+        }
+
+        assert!(rem_server.is_empty());
+        assert!(matches!(results[0], Response::Ok(_)));
+        assert!(matches!(results[1], Response::Err(_)));
+    }
+
     #[test]
     fn test_example_session() {
         let client = b"\
@@ -604,112 +1223,326 @@ QUIT
             println!("{:#?}", cmd);
             rem_client = rem;
 
-            use Command::*;
-            match cmd {
-                User(_) => {
-                    let (rem, resp) = response_user(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Pass(_) => {
-                    let (rem, resp) = response_pass(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Stat => {
-                    let (rem, resp) = response_stat(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                ListAll => {
-                    let (rem, resp) = response_list_all(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                List { .. } => {
-                    let (rem, resp) = response_list(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Retr { .. } => {
-                    let (rem, resp) = response_retr(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Dele { .. } => {
-                    let (rem, resp) = response_dele(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Noop => {
-                    let (rem, resp) = response_noop(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Rset => {
-                    let (rem, resp) = response_rset(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Quit => {
-                    let (rem, resp) = response_quit(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Apop { .. } => {
-                    let (rem, resp) = response_apop(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Top { .. } => {
-                    let (rem, resp) = response_top(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                UidlAll => {
-                    let (rem, resp) = response_uidl_all(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Uidl { .. } => {
-                    let (rem, resp) = response_uidl(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Capa => {
-                    let (rem, resp) = response_user(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Stls => {
-                    let (rem, resp) = response_stls(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                AuthAll => {
-                    let (rem, resp) = response_auth_all(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Auth { .. } => {
-                    unimplemented!()
-                }
-                Utf8 => {
-                    let (rem, resp) = response_utf8(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                LangAll => {
-                    let (rem, resp) = response_lang_all(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
-                }
-                Lang { .. } => {
-                    let (rem, resp) = response_lang(rem_server).unwrap();
-                    println!("{:#?}", resp);
-                    rem_server = rem;
+            loop {
+                let (rem, resp) = response(&cmd, rem_server).unwrap();
+                println!("{:#?}", resp);
+                rem_server = rem;
+
+                match resp {
+                    AnyResponse::Auth(AuthResponse::Continue(_)) => {
+                        let (rem, data) = auth_data(rem_client).unwrap();
+                        println!("{:#?}", data);
+                        rem_client = rem;
+                    }
+                    _ => break,
                 }
             }
         }
+
+        assert!(rem_server.is_empty());
+    }
+
+    #[test]
+    fn test_response_dispatch() {
+        let tests: &[(Command, &[u8])] = &[
+            (Command::Capa, b"+OK\r\nTOP\r\nUIDL\r\n.\r\n"),
+            (Command::Stat, b"+OK 2 320\r\n"),
+            (Command::ListAll, b"+OK\r\n1 120\r\n.\r\n"),
+            (
+                Command::List {
+                    msg: MessageNumber::new(1).unwrap(),
+                },
+                b"-ERR no such message\r\n",
+            ),
+            (
+                Command::Retr {
+                    msg: MessageNumber::new(1).unwrap(),
+                },
+                b"+OK\r\n..\r\n.\r\n",
+            ),
+            (
+                Command::Auth {
+                    mechanism: Mechanism::Plain,
+                    initial_response: None,
+                },
+                b"+ \r\n",
+            ),
+            (Command::Quit, b"+OK bye\r\n"),
+        ];
+
+        for (command, test) in tests {
+            let (rem, got) = response(command, test).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(got.serialize(), *test);
+        }
+
+        assert!(matches!(
+            response(&Command::Capa, b"+OK\r\nTOP\r\n.\r\n").unwrap().1,
+            AnyResponse::Capa(Response::Ok(_))
+        ));
+        assert!(response(&Command::Stat, b"+OK\r\n").is_err());
+    }
+
+    #[test]
+    fn test_borrowed() {
+        let input = b"+OK 1 whqtswO00WBw418f9t5JxYwZ\r\n".to_vec();
+        let (_, got) = response_uidl(&input).unwrap();
+
+        let owned = match got {
+            Response::Ok(listing) => {
+                assert!(matches!(listing.message_uid, Cow::Borrowed(_)));
+                listing.into_owned()
+            }
+            Response::Err(_) => panic!(),
+        };
+        drop(input);
+        assert_eq!(owned.message_uid, "whqtswO00WBw418f9t5JxYwZ");
+
+        // Unstuffing only removes the leading ".", i.e., lines are borrowed as well.
+        let (_, got) = response_retr(b"+OK\r\nfoo\r\n..bar\r\n.\r\n").unwrap();
+        match got {
+            Response::Ok(message) => {
+                assert!(matches!(message.body[0], Cow::Borrowed(_)));
+                assert_eq!(message.body[1], Cow::Borrowed(&b".bar"[..]));
+            }
+            Response::Err(_) => panic!(),
+        }
+
+        let (_, got) = command(b"USER alice\r\n").unwrap();
+        assert!(matches!(got, Command::User(Cow::Borrowed("alice"))));
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(
+            response_stat(b"+OK 2 5000000000\r\n"),
+            Ok((
+                &b""[..],
+                Response::Ok(DropListing {
+                    message_count: 2,
+                    maildrop_size: 5_000_000_000,
+                    trailing: None,
+                })
+            ))
+        );
+        assert_eq!(
+            response_list(b"+OK 1 4294967296\r\n"),
+            Ok((
+                &b""[..],
+                Response::Ok(ScanListing {
+                    message_id: MessageNumber::new(1).unwrap(),
+                    message_size: 4_294_967_296,
+                    trailing: None,
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_trailing() {
+        let (_, stat) = response_stat(b"+OK 2 320 messages\r\n").unwrap();
+        assert_eq!(
+            stat,
+            Response::Ok(DropListing {
+                message_count: 2,
+                maildrop_size: 320,
+                trailing: Some(Cow::Borrowed("messages")),
+            })
+        );
+
+        let (_, list) = response_list(b"+OK 1 120 octets (flagged)\r\n").unwrap();
+        assert_eq!(
+            list,
+            Response::Ok(ScanListing {
+                message_id: MessageNumber::new(1).unwrap(),
+                message_size: 120,
+                trailing: Some(Cow::Borrowed("octets (flagged)")),
+            })
+        );
+
+        let (_, uidl) = response_uidl_all(b"+OK\r\n1 abc seen\r\n2 def\r\n.\r\n").unwrap();
+        let body = match uidl {
+            Response::Ok(uidl) => uidl.body,
+            Response::Err(_) => panic!(),
+        };
+        assert_eq!(body[0].trailing, Some(Cow::Borrowed("seen")));
+        assert_eq!(body[1].trailing, None);
+
+        // The text must be separated by a space.
+        assert!(response_stat(b"+OK 2 320messages\r\n").is_err());
+    }
+
+    #[test]
+    fn test_quirks() {
+        let strict = ParseOptions::strict();
+        let permissive = ParseOptions::permissive();
+
+        let tests: &[&[u8]] = &[
+            // Bare LF
+            b"+OK 2 320\n",
+            // Double spaces
+            b"+OK  2  320\r\n",
+            // Missing space
+            b"+OK2 320\r\n",
+            // Lowercase status
+            b"+ok 2 320\r\n",
+        ];
+
+        for test in tests {
+            assert!(strict.response_stat(test).is_err());
+            assert_eq!(
+                permissive.response_stat(test).unwrap().1,
+                Response::Ok(DropListing {
+                    message_count: 2,
+                    maildrop_size: 320,
+                    trailing: None,
+                })
+            );
+        }
+
+        // The default accepts bare LF and lowercase status indicators.
+        assert!(response_stat(b"+ok 2 320\n").is_ok());
+        assert!(response_stat(b"+OK2 320\r\n").is_err());
+
+        assert_eq!(
+            permissive.response_user(b"+OKwelcome\r\n").unwrap().1,
+            Response::Ok(SingleLine {
+                code: None,
+                comment: "welcome".into(),
+            })
+        );
+        assert!(strict.response_user(b"+OKwelcome\r\n").is_err());
+
+        let body = b"+OK\n1  120\n.\n";
+        assert!(strict.response_list_all(body).is_err());
+        assert!(response_list_all(body).is_err());
+        assert_eq!(
+            permissive.response_list_all(body).unwrap().1,
+            Response::Ok(MultiLine {
+                head: SingleLine {
+                    code: None,
+                    comment: "".into(),
+                },
+                body: vec![ScanListing {
+                    message_id: MessageNumber::new(1).unwrap(),
+                    message_size: 120,
+                    trailing: None,
+                }],
+            })
+        );
+
+        let mut line = b"+OK ".to_vec();
+        line.extend_from_slice(&[b'a'; 600]);
+        line.extend_from_slice(b"\r\n");
+        assert_eq!(
+            strict.greeting(&line),
+            Err(DecodeError::LineTooLong { limit: 512 })
+        );
+        assert_eq!(permissive.greeting(&line).unwrap().1.comment.len(), 600);
+
+        assert!(strict.command(b"STAT\n").is_err());
+        assert_eq!(strict.command(b"STAT\r\n").unwrap().1, Command::Stat);
+        assert_eq!(permissive.command(b"STAT\n").unwrap().1, Command::Stat);
+
+        let capa = b"+OK\nTOP\nSASL  PLAIN\nEXPIRE  NEVER  USER\n.\n";
+        assert!(strict.response_capa(capa).is_err());
+        assert!(strict
+            .response_capa(b"+OK\r\nSASL  PLAIN\r\n.\r\n")
+            .is_err());
+        assert_eq!(
+            permissive.response_capa(capa).unwrap().1,
+            Response::Ok(MultiLine {
+                head: SingleLine {
+                    code: None,
+                    comment: "".into(),
+                },
+                body: vec![
+                    Capability::Top,
+                    Capability::Sasl {
+                        mechanisms: vec![Mechanism::Plain],
+                    },
+                    Capability::Expire {
+                        policy: ExpirePolicy::Never,
+                        per_user: true,
+                    },
+                ],
+            })
+        );
+
+        // A bare LF is part of the line unless it is accepted as line ending.
+        let retr = b"+OK\r\nSubject: Test\n\r\n.\r\n";
+        match strict.response_retr(retr).unwrap().1 {
+            Response::Ok(message) => assert_eq!(message.body, vec![&b"Subject: Test\n"[..]]),
+            Response::Err(_) => panic!(),
+        }
+        match permissive.response_retr(retr).unwrap().1 {
+            Response::Ok(message) => {
+                assert_eq!(message.body, vec![&b"Subject: Test"[..], &b""[..]])
+            }
+            Response::Err(_) => panic!(),
+        }
+        match permissive
+            .response_retr(b"+OK\nSubject: Test\n.\n")
+            .unwrap()
+            .1
+        {
+            Response::Ok(message) => assert_eq!(message.body, vec![&b"Subject: Test"[..]]),
+            Response::Err(_) => panic!(),
+        }
+        assert!(strict.response_retr(b"+OK\nSubject: Test\n.\n").is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            response_stat(b"+OK 2 "),
+            Err(DecodeError::Incomplete { .. })
+        ));
+        assert_eq!(
+            response_stat(b"+OK 2 x\r\n"),
+            Err(DecodeError::Failed {
+                offset: 6,
+                expected: "number"
+            })
+        );
+        assert_eq!(
+            command(b"FOO\r\n"),
+            Err(DecodeError::Failed {
+                offset: 0,
+                expected: "keyword"
+            })
+        );
+
+        // Message numbers start at 1.
+        assert!(matches!(
+            command(b"RETR 0\r\n"),
+            Err(DecodeError::Failed { .. })
+        ));
+        assert!(matches!(
+            response_list(b"+OK 0 120\r\n"),
+            Err(DecodeError::Failed { offset: 4, .. })
+        ));
+        assert!(matches!(
+            response_uidl_all(b"+OK\r\n0 abc\r\n.\r\n"),
+            Err(DecodeError::Failed { offset: 5, .. })
+        ));
+
+        let mut line = b"USER ".to_vec();
+        line.extend_from_slice(&[b'a'; 300]);
+        assert_eq!(command(&line), Err(DecodeError::LineTooLong { limit: 255 }));
+
+        let mut line = b"+OK ".to_vec();
+        line.extend_from_slice(&[b'a'; 600]);
+        line.extend_from_slice(b"\r\n");
+        assert_eq!(
+            response_noop(&line),
+            Err(DecodeError::LineTooLong { limit: 512 })
+        );
+
+        // Only the status line is limited.
+        let mut body = b"+OK\r\n".to_vec();
+        body.extend_from_slice(&[b'a'; 600]);
+        body.extend_from_slice(b"\r\n.\r\n");
+        assert!(response_retr(&body).is_ok());
     }
 }
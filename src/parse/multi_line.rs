@@ -0,0 +1,351 @@
+use std::{borrow::Cow, marker::PhantomData};
+
+use nom::{bytes::streaming::tag, sequence::tuple};
+
+use crate::{
+    error::{DecodeError, DecodeResult},
+    parse::{
+        finish,
+        response::{capability, dot_stuffed, language_listing, scan_listing, unique_id_listing},
+        ParseOptions,
+    },
+    types::response::{Capability, LanguageListing, ScanListing, UniqueIdListing},
+};
+
+/// Line in the body of a multi-line response, which can be parsed on its own.
+///
+/// Implement this to use the [MultiLineIter] for responses without a dedicated parser.
+pub trait ParseLine<'a>: Sized {
+    /// Parses the line (without CRLF), i.e., the line ending must be the remaining input.
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self>;
+}
+
+impl<'a> ParseLine<'a> for ScanListing<'a> {
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self> {
+        finish(input, None, |input| scan_listing(input, options))
+    }
+}
+
+impl<'a> ParseLine<'a> for UniqueIdListing<'a> {
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self> {
+        finish(input, None, |input| unique_id_listing(input, options))
+    }
+}
+
+impl<'a> ParseLine<'a> for LanguageListing<'a> {
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self> {
+        finish(input, None, |input| language_listing(input, options))
+    }
+}
+
+impl<'a> ParseLine<'a> for Capability<'a> {
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self> {
+        finish(input, None, |input| capability(input, options))
+    }
+}
+
+/// The (unstuffed) line of a message, see [response_retr](crate::parse::response_retr).
+impl<'a> ParseLine<'a> for Cow<'a, [u8]> {
+    fn parse_line(input: &'a [u8], options: &ParseOptions) -> DecodeResult<'a, Self> {
+        finish(input, None, |input| dot_stuffed(input, options))
+    }
+}
+
+/// Lazy iterator over the body of a multi-line response, e.g., the response to LIST or UIDL.
+///
+/// In contrast to [response_list_all](crate::parse::response_list_all), the lines are not
+/// collected but parsed one at a time. Every item is either a parsed line or an error:
+///
+/// * A malformed line is returned as [DecodeError::Failed] with the offset of the error and
+///   skipped, i.e., the iteration continues with the next line.
+/// * When the input ends before the terminating "." line, [DecodeError::Incomplete] is returned
+///   and the iteration ends. [is_done](MultiLineIter::is_done) tells whether the terminator was
+///   received.
+///
+/// Offsets are relative to the start of the response when the iterator was created by
+/// [response_list_all_iter](crate::parse::response_list_all_iter) or
+/// [response_uidl_all_iter](crate::parse::response_uidl_all_iter).
+///
+/// To read from a streaming source, drop the [consumed](MultiLineIter::consumed) bytes, receive
+/// more data, and [resume](MultiLineIter::resume) at the current
+/// [offset](MultiLineIter::offset):
+///
+/// ```rust
+/// use pop3_codec::{
+///     parse::{response_list_all_iter, MultiLineIter},
+///     types::{IntoOwned, Response, ScanListing},
+/// };
+///
+/// let chunks: &[&[u8]] = &[b"+OK\r\n1 120\r\n2 2", b"00\r\n3 5x\r\n", b".\r\n"];
+/// let mut chunks = chunks.iter();
+/// let mut buffer = chunks.next().unwrap().to_vec();
+/// let mut listings = Vec::new();
+///
+/// let (rem, response) = response_list_all_iter(&buffer).unwrap();
+/// assert!(matches!(response, Response::Ok(_)));
+///
+/// let mut offset = buffer.len() - rem.len();
+/// buffer.drain(..offset);
+///
+/// loop {
+///     let mut lines = MultiLineIter::<ScanListing>::resume(&buffer, offset);
+///
+///     for line in &mut lines {
+///         match line {
+///             Ok(listing) => listings.push(listing.into_owned()),
+///             Err(error) => println!("{}", error),
+///         }
+///     }
+///
+///     if lines.is_done() {
+///         break;
+///     }
+///
+///     offset = lines.offset();
+///     let consumed = lines.consumed();
+///     buffer.drain(..consumed);
+///     buffer.extend_from_slice(chunks.next().unwrap());
+/// }
+///
+/// assert_eq!(listings.len(), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiLineIter<'a, T> {
+    input: &'a [u8],
+    /// Offset of `input` within the response.
+    offset: usize,
+    consumed: usize,
+    state: IterState,
+    options: ParseOptions,
+    marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IterState {
+    Lines,
+    /// More input is required.
+    Incomplete,
+    /// The terminating "." line was received.
+    Done,
+}
+
+impl<'a, T> MultiLineIter<'a, T>
+where
+    T: ParseLine<'a>,
+{
+    /// Iterates over the lines in `input`, which must start after the status line.
+    pub fn new(input: &'a [u8]) -> Self {
+        Self::resume(input, 0)
+    }
+
+    /// Continues an iteration over `input`, which starts at `offset` within the response.
+    pub fn resume(input: &'a [u8], offset: usize) -> Self {
+        Self {
+            input,
+            offset,
+            consumed: 0,
+            state: IterState::Lines,
+            options: ParseOptions::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Parses the lines with `options` instead of the default ones.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns `true` when the terminating "." line was received.
+    pub fn is_done(&self) -> bool {
+        self.state == IterState::Done
+    }
+
+    /// Returns the number of consumed bytes of the input, i.e., complete lines (including skipped
+    /// ones) and the terminator.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Returns the offset of the first unconsumed byte within the response.
+    pub fn offset(&self) -> usize {
+        self.offset + self.consumed
+    }
+
+    fn incomplete(&mut self, error: DecodeError) -> Option<Result<T, DecodeError>> {
+        self.state = IterState::Incomplete;
+
+        Some(Err(error))
+    }
+
+    /// Skips the malformed line and returns the error with its offset within the response.
+    fn skip(&mut self, error: DecodeError) -> Option<Result<T, DecodeError>> {
+        let rem = &self.input[self.consumed..];
+
+        let end = match rem.iter().position(|byte| *byte == b'\n') {
+            Some(end) => end,
+            // The error is reported as soon as the end of the line is received.
+            None => return self.incomplete(DecodeError::Incomplete { needed: None }),
+        };
+
+        let error = match error {
+            DecodeError::Failed { offset, expected } => DecodeError::Failed {
+                offset: self.offset() + offset,
+                expected,
+            },
+            error => error,
+        };
+
+        self.consumed += end + 1;
+
+        Some(Err(error))
+    }
+}
+
+impl<'a, T> Iterator for MultiLineIter<'a, T>
+where
+    T: ParseLine<'a>,
+{
+    type Item = Result<T, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state != IterState::Lines {
+            return None;
+        }
+
+        let rem = &self.input[self.consumed..];
+
+        let options = self.options;
+
+        match finish(rem, None, tuple((tag("."), |i| options.line_ending(i)))) {
+            Ok((after, _)) => {
+                self.consumed = self.input.len() - after.len();
+                self.state = IterState::Done;
+                return None;
+            }
+            Err(DecodeError::Incomplete { needed }) => {
+                return self.incomplete(DecodeError::Incomplete { needed })
+            }
+            // Not the terminator.
+            Err(_) => {}
+        }
+
+        let (after, item) = match T::parse_line(rem, &options) {
+            Ok(parsed) => parsed,
+            Err(DecodeError::Incomplete { needed }) => {
+                return self.incomplete(DecodeError::Incomplete { needed })
+            }
+            Err(error) => return self.skip(error),
+        };
+
+        match finish(after, None, |i| options.line_ending(i)) {
+            Ok((after, _)) => {
+                self.consumed = self.input.len() - after.len();
+
+                Some(Ok(item))
+            }
+            Err(DecodeError::Incomplete { needed }) => {
+                self.incomplete(DecodeError::Incomplete { needed })
+            }
+            Err(DecodeError::Failed { expected, .. }) => self.skip(DecodeError::Failed {
+                offset: rem.len() - after.len(),
+                expected,
+            }),
+            Err(error) => self.skip(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parse::{response_list_all_iter, response_uidl_all_iter},
+        types::{MessageNumber, Response},
+    };
+
+    #[test]
+    fn test_multi_line_iter() {
+        let input = b"+OK 3 messages\r\n1 120\r\n2 x\r\n3 300\r\n.\r\n-ERR";
+        let (rem, response) = response_list_all_iter(input).unwrap();
+
+        let (head, mut lines) = match response {
+            Response::Ok(ok) => ok,
+            Response::Err(_) => panic!(),
+        };
+        assert_eq!(head.comment, "3 messages");
+        assert_eq!(rem, &input[16..]);
+
+        assert_eq!(
+            lines.next(),
+            Some(Ok(ScanListing {
+                message_id: MessageNumber::new(1).unwrap(),
+                message_size: 120,
+                trailing: None,
+            }))
+        );
+        // The offset of "x".
+        assert!(matches!(
+            lines.next(),
+            Some(Err(DecodeError::Failed { offset: 25, .. }))
+        ));
+        assert_eq!(
+            lines.next(),
+            Some(Ok(ScanListing {
+                message_id: MessageNumber::new(3).unwrap(),
+                message_size: 300,
+                trailing: None,
+            }))
+        );
+        assert_eq!(lines.next(), None);
+        assert!(lines.is_done());
+        assert_eq!(lines.offset(), input.len() - 4);
+        assert_eq!(lines.next(), None);
+
+        let (rem, response) = response_uidl_all_iter(b"-ERR no\r\n+OK").unwrap();
+        assert_eq!(rem, b"+OK");
+        assert!(matches!(response, Response::Err(_)));
+    }
+
+    #[test]
+    fn test_multi_line_iter_incomplete() {
+        let tests: &[&[u8]] = &[b"", b"1 12", b"1 120\r", b".", b".\r", b"1 x"];
+
+        for test in tests {
+            let mut lines = MultiLineIter::<ScanListing>::new(test);
+            assert!(matches!(
+                lines.next(),
+                Some(Err(DecodeError::Incomplete { .. }))
+            ));
+            assert_eq!(lines.next(), None);
+            assert!(!lines.is_done());
+            assert_eq!(lines.consumed(), 0);
+        }
+
+        // A malformed line is reported as soon as its end was received.
+        let mut lines =
+            MultiLineIter::<UniqueIdListing>::resume(b"1 whqtswO00WBw418f9t5JxYwZ\r\n..", 7);
+        assert!(matches!(lines.next(), Some(Ok(_))));
+        assert!(matches!(
+            lines.next(),
+            Some(Err(DecodeError::Incomplete { .. }))
+        ));
+        assert_eq!(lines.offset(), 35);
+
+        let mut lines = MultiLineIter::<UniqueIdListing>::resume(b"..\r\n", 35);
+        assert!(matches!(
+            lines.next(),
+            Some(Err(DecodeError::Failed { offset: 35, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_multi_line_iter_body() {
+        let mut lines = MultiLineIter::<Cow<[u8]>>::new(b"..foo\r\nbar\r\n.\r\n");
+
+        assert_eq!(lines.next(), Some(Ok(Cow::Borrowed(&b".foo"[..]))));
+        assert_eq!(lines.next(), Some(Ok(Cow::Borrowed(&b"bar"[..]))));
+        assert_eq!(lines.next(), None);
+        assert!(lines.is_done());
+    }
+}
@@ -0,0 +1,135 @@
+use nom::{
+    bytes::streaming::{tag, tag_no_case, take_until, take_while1},
+    character::streaming::{crlf, line_ending, not_line_ending},
+    combinator::{map, opt},
+    IResult,
+};
+
+/// Quirks of real-world implementations, which the parsers should tolerate.
+///
+/// Every field enables one deviation from the ABNF. Use [strict](ParseOptions::strict) for
+/// conformance testing and [permissive](ParseOptions::permissive) for interoperability. The
+/// default tolerates bare LF line endings and status indicators in any case, i.e., what the
+/// parsers always accepted.
+///
+/// Commands are only affected by `bare_lf` and `long_lines`.
+///
+/// ```rust
+/// use pop3_codec::parse::{response_stat, ParseOptions};
+///
+/// let input = b"+OK  2 320\n";
+///
+/// assert!(response_stat(input).is_err());
+/// assert!(ParseOptions::permissive().response_stat(input).is_ok());
+/// ```
+#[cfg_attr(feature = "serdex", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    /// Accept "\n" as line ending, e.g., "+OK\n".
+    pub bare_lf: bool,
+    /// Accept multiple spaces where a single space is expected, e.g., "+OK 2  320".
+    pub extra_spaces: bool,
+    /// Accept text directly after the status indicator, e.g., "+OK2 320".
+    pub missing_space: bool,
+    /// Accept status indicators in any case, e.g., "+ok".
+    pub lowercase_status: bool,
+    /// Do not limit the length of status lines and commands, e.g., to accept greetings longer
+    /// than 512 octets.
+    pub long_lines: bool,
+}
+
+impl ParseOptions {
+    /// Follows the ABNF (and its corrections) without tolerating any quirks.
+    pub fn strict() -> Self {
+        Self {
+            bare_lf: false,
+            extra_spaces: false,
+            missing_space: false,
+            lowercase_status: false,
+            long_lines: false,
+        }
+    }
+
+    /// Tolerates all quirks.
+    pub fn permissive() -> Self {
+        Self {
+            bare_lf: true,
+            extra_spaces: true,
+            missing_space: true,
+            lowercase_status: true,
+            long_lines: true,
+        }
+    }
+
+    /// Returns `limit` unless long lines are accepted.
+    pub(crate) fn limit(&self, limit: usize) -> Option<usize> {
+        if self.long_lines {
+            None
+        } else {
+            Some(limit)
+        }
+    }
+
+    /// CRLF, or LF when `bare_lf` is set.
+    pub(crate) fn line_ending<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        if self.bare_lf {
+            line_ending(input)
+        } else {
+            crlf(input)
+        }
+    }
+
+    /// Everything up to the line ending, i.e., a bare LF is part of the line unless `bare_lf` is
+    /// set.
+    pub(crate) fn not_line_ending<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        if self.bare_lf {
+            not_line_ending(input)
+        } else {
+            take_until("\r\n")(input)
+        }
+    }
+
+    /// SP, or 1*SP when `extra_spaces` is set.
+    pub(crate) fn sp<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        if self.extra_spaces {
+            take_while1(|byte| byte == b' ')(input)
+        } else {
+            tag(" ")(input)
+        }
+    }
+
+    /// The separator after a status indicator, i.e., SP, which is optional when `missing_space`
+    /// is set.
+    pub(crate) fn status_sp<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        if self.missing_space {
+            map(opt(|input| self.sp(input)), |_| ())(input)
+        } else {
+            map(|input| self.sp(input), |_| ())(input)
+        }
+    }
+
+    /// Matches the status indicator `status`, ignoring case when `lowercase_status` is set.
+    pub(crate) fn status<'a>(
+        &self,
+        status: &'static str,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], &'a [u8]> {
+        if self.lowercase_status {
+            tag_no_case(status)(input)
+        } else {
+            tag(status)(input)
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            bare_lf: true,
+            extra_spaces: false,
+            missing_space: false,
+            lowercase_status: true,
+            long_lines: false,
+        }
+    }
+}
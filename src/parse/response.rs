@@ -1,11 +1,11 @@
-use std::str::from_utf8;
+use std::{borrow::Cow, str::from_utf8};
 
 use abnf_core::streaming::SP;
 use nom::{
     branch::alt,
     bytes::streaming::{tag, tag_no_case, take_till, take_while, take_while1, take_while_m_n},
-    character::streaming::{line_ending, not_line_ending},
-    combinator::{map, map_res, opt, value},
+    character::streaming::not_line_ending,
+    combinator::{map, map_res, opt, peek, value, verify},
     error::ErrorKind,
     multi::{many0, separated_list1},
     sequence::{delimited, preceded, separated_pair, terminated, tuple},
@@ -13,10 +13,13 @@ use nom::{
 };
 
 use crate::{
-    parse::{language, number, param},
+    parse::{
+        command::{auth_type, base64},
+        language_tag, message_number, number, param, size, ParseOptions,
+    },
     types::response::{
-        Capability, DropListing, ExpirePolicy, LanguageListing, MultiLine, Response, ScanListing,
-        SingleLine, UniqueIdListing,
+        Capability, DropListing, ExpirePolicy, LanguageListing, MultiLine, Response, ResponseCode,
+        ScanListing, SingleLine, UniqueIdListing,
     },
 };
 
@@ -80,34 +83,38 @@ pub(crate) fn timestamp(input: &[u8]) -> IResult<&[u8], &str> {
 ///
 /// Edit: we use a version, where [SP text] is replaced by a `parser` and use it for
 /// positive responses. Negative responses are parsed as showed in the single-line ABNF.
-pub(crate) fn single_line<P, O>(
-    input: &[u8],
+pub(crate) fn single_line<'a, P, O>(
+    input: &'a [u8],
+    options: &ParseOptions,
     parser: P,
     payload_required: bool,
-) -> IResult<&[u8], Response<O, SingleLine>>
+) -> IResult<&'a [u8], Response<O, SingleLine<'a>>>
 where
-    P: Fn(&[u8]) -> IResult<&[u8], O>,
+    P: Fn(&'a [u8]) -> IResult<&'a [u8], O>,
     O: std::fmt::Debug + Clone + PartialEq + Eq,
 {
-    let (rem, status) = status(input)?;
+    let (rem, status) = status(input, options)?;
 
     match status {
         Status::Ok => {
             let rem = if payload_required {
-                let (rem, _) = SP(rem)?;
+                let (rem, _) = options.status_sp(rem)?;
                 rem
             } else {
                 rem
             };
 
-            let mut parser = tuple((parser, line_ending));
+            let mut parser = tuple((parser, |input| options.line_ending(input)));
 
             let (rem, (something, _)) = parser(rem)?;
 
             Ok((rem, Response::Ok(something)))
         }
         Status::Err => {
-            let mut parser = tuple((head, line_ending));
+            let mut parser = tuple((
+                |input| head(input, options),
+                |input| options.line_ending(input),
+            ));
 
             let (rem, (head, _)) = parser(rem)?;
 
@@ -116,54 +123,75 @@ where
     }
 }
 
-pub(crate) fn head(input: &[u8]) -> IResult<&[u8], SingleLine> {
-    let mut parser = opt(preceded(SP, text));
+pub(crate) fn head<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], SingleLine<'a>> {
+    let mut parser = opt(preceded(|input| options.status_sp(input), text));
 
     let (rem, maybe_text) = parser(input)?;
 
     let (code, comment) = match maybe_text {
         Some((code, comment)) => {
-            let code = code.into_iter().map(|lvl| lvl.to_owned()).collect();
-
-            let comment = comment.to_owned();
+            let code = code.map(|levels| ResponseCode::from_levels(&levels));
 
-            (code, comment)
+            (code, Cow::Borrowed(comment))
         }
-        None => (Vec::default(), String::default()),
+        None => (None, Cow::Borrowed("")),
     };
 
     Ok((rem, SingleLine { code, comment }))
 }
 
-pub(crate) fn drop_listing(input: &[u8]) -> IResult<&[u8], DropListing> {
-    let mut parser = separated_pair(number, SP, number);
+/// drop-listing = number SP number [SP text]
+///
+/// Note: RFC 1939 allows servers to append information, e.g., "+OK 2 320 messages".
+pub(crate) fn drop_listing<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], DropListing<'a>> {
+    let mut parser = tuple((number, |input| options.sp(input), size, trailing));
 
-    let (rem, (message_count, maildrop_size)) = parser(input)?;
+    let (rem, (message_count, _, maildrop_size, trailing)) = parser(input)?;
 
     Ok((
         rem,
         DropListing {
             message_count,
             maildrop_size,
+            trailing: trailing.map(Cow::Borrowed),
         },
     ))
 }
 
-pub(crate) fn scan_listing(input: &[u8]) -> IResult<&[u8], ScanListing> {
-    let mut parser = separated_pair(number, SP, number);
+/// scan-listing = number SP number [SP text]
+pub(crate) fn scan_listing<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], ScanListing<'a>> {
+    let mut parser = tuple((message_number, |input| options.sp(input), size, trailing));
 
-    let (rem, (message_id, message_size)) = parser(input)?;
+    let (rem, (message_id, _, message_size, trailing)) = parser(input)?;
 
     Ok((
         rem,
         ScanListing {
             message_id,
             message_size,
+            trailing: trailing.map(Cow::Borrowed),
         },
     ))
 }
 
-pub(crate) fn unique_id_listing(input: &[u8]) -> IResult<&[u8], UniqueIdListing> {
+/// Optional text after a listing, i.e., [SP text] up to the line ending.
+pub(crate) fn trailing(input: &[u8]) -> IResult<&[u8], Option<&str>> {
+    opt(preceded(SP, map_res(not_line_ending, from_utf8)))(input)
+}
+
+pub(crate) fn unique_id_listing<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], UniqueIdListing<'a>> {
     // The unique-id of a message is an arbitrary server-determined
     // string, consisting of one to 70 characters in the range 0x21
     // to 0x7E, ...
@@ -177,29 +205,42 @@ pub(crate) fn unique_id_listing(input: &[u8]) -> IResult<&[u8], UniqueIdListing>
         })(input)
     }
 
-    let mut parser = separated_pair(number, SP, unique_id);
+    let mut parser = tuple((
+        message_number,
+        |input| options.sp(input),
+        unique_id,
+        trailing,
+    ));
 
-    let (rem, (message_id, message_uid)) = parser(input)?;
+    let (rem, (message_id, _, message_uid, trailing)) = parser(input)?;
 
     Ok((
         rem,
         UniqueIdListing {
             message_id,
-            message_uid: message_uid.to_owned(),
+            message_uid: Cow::Borrowed(message_uid),
+            trailing: trailing.map(Cow::Borrowed),
         },
     ))
 }
 
-pub(crate) fn language_listing(input: &[u8]) -> IResult<&[u8], LanguageListing> {
-    let mut parser = separated_pair(language, SP, map_res(not_line_ending, from_utf8));
+pub(crate) fn language_listing<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], LanguageListing<'a>> {
+    let mut parser = separated_pair(
+        language_tag,
+        |input| options.sp(input),
+        map_res(not_line_ending, from_utf8),
+    );
 
     let (rem, (tag, description)) = parser(input)?;
 
     Ok((
         rem,
         LanguageListing {
-            tag: tag.to_owned(),
-            description: description.to_owned(),
+            tag,
+            description: Cow::Borrowed(description),
         },
     ))
 }
@@ -214,30 +255,42 @@ enum Status {
 }
 
 /// status = "+OK" / "-ERR"
-fn status(input: &[u8]) -> IResult<&[u8], Status> {
+fn status<'a>(input: &'a [u8], options: &ParseOptions) -> IResult<&'a [u8], Status> {
     alt((
-        value(Status::Ok, tag_no_case("+OK")),
-        value(Status::Err, tag_no_case("-ERR")),
+        value(Status::Ok, |input| options.status("+OK", input)),
+        value(Status::Err, |input| options.status("-ERR", input)),
     ))(input)
 }
 
 /// text = *schar / resp-code *CHAR
 ///
 /// Corrections:
-/// resp-code --> resp-code SP
+/// * resp-code *CHAR --> resp-code [SP *CHAR]
 /// * *CHAR --> <read until \r\n excluding NULL>
-/// *schar also matches empty sequence...
-fn text(input: &[u8]) -> IResult<&[u8], (Vec<&str>, &str)> {
+/// * *schar also matches empty sequence...
+///
+/// Extension:
+/// * SP *CHAR containing "[" --> escaped comment without the SP (see `SingleLine` serialization)
+fn text(input: &[u8]) -> IResult<&[u8], (Option<Vec<&str>>, &str)> {
     let mut parser = alt((
         map(
             tuple((
-                terminated(resp_code, SP),
-                map_res(not_line_ending, from_utf8),
+                resp_code,
+                opt(preceded(SP, map_res(not_line_ending, from_utf8))),
             )),
-            |(code, comment)| (code, comment),
+            |(code, comment)| (Some(code), comment.unwrap_or_default()),
+        ),
+        map(
+            preceded(
+                SP,
+                verify(map_res(not_line_ending, from_utf8), |comment: &str| {
+                    comment.contains('[')
+                }),
+            ),
+            |comment| (None, comment),
         ),
         map(map_res(take_while(is_schar), from_utf8), |comment| {
-            (vec![], comment)
+            (None, comment)
         }),
     ));
 
@@ -263,21 +316,22 @@ fn is_schar(byte: u8) -> bool {
 // ----- ## Multi Line -----
 
 /// multi-line = single-line *dot-stuffed "." CRLF
-pub(crate) fn multi_line<P, O>(
-    input: &[u8],
+pub(crate) fn multi_line<'a, P, O>(
+    input: &'a [u8],
+    options: &ParseOptions,
     parser: P,
-) -> IResult<&[u8], Response<MultiLine<O>, SingleLine>>
+) -> IResult<&'a [u8], Response<MultiLine<'a, O>, SingleLine<'a>>>
 where
-    P: Fn(&[u8]) -> IResult<&[u8], O>,
+    P: Fn(&'a [u8]) -> IResult<&'a [u8], O>,
     O: std::fmt::Debug + Clone + PartialEq + Eq,
 {
-    let (rem, single) = single_line(input, head, false)?;
+    let (rem, single) = single_line(input, options, |input| head(input, options), false)?;
 
     match single {
         Response::Ok(head) => {
             let mut parser = tuple((
-                many0(terminated(parser, line_ending)),
-                tuple((tag("."), line_ending)),
+                many0(terminated(parser, |input| options.line_ending(input))),
+                tuple((tag("."), |input| options.line_ending(input))),
             ));
 
             let (rem, (something, _)) = parser(rem)?;
@@ -301,21 +355,51 @@ where
 // Note: must be dot-stuffed
 //
 // Note: Do not consume CRLF, because it is done in higher-level multi-line parser
-pub(crate) fn dot_stuffed(input: &[u8]) -> IResult<&[u8], String> {
+//
+// Note: The line is returned "unstuffed", i.e., a leading "." is removed (see RFC 1939, section 3).
+pub(crate) fn dot_stuffed<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Cow<'a, [u8]>> {
     // Read until \r\n ...
-    let mut parser = map_res(not_line_ending, from_utf8);
-
-    let (rem, line) = parser(input)?;
+    let (rem, line) = options.not_line_ending(input)?;
 
-    // ... and accept every line, which is not "."
-    if line == "." {
-        Err(nom::Err::Error(nom::error::Error::new(
+    // ... and accept every line, which is not "." ...
+    if line == b"." {
+        return Err(nom::Err::Error(nom::error::Error::new(
             input,
             ErrorKind::IsNot,
-        )))
-    } else {
-        Ok((rem, line.to_owned()))
+        )));
     }
+
+    // ... but remove the termination octet from byte-stuffed lines.
+    match line.strip_prefix(b".") {
+        Some(unstuffed) => Ok((rem, Cow::Borrowed(unstuffed))),
+        None => Ok((rem, Cow::Borrowed(line))),
+    }
+}
+
+/// Same as `dot_stuffed`, but requires the line to be valid UTF-8.
+pub(crate) fn dot_stuffed_utf8<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Cow<'a, str>> {
+    map_res(
+        |input| dot_stuffed(input, options),
+        |line| match line {
+            Cow::Borrowed(line) => from_utf8(line).map(Cow::Borrowed),
+            Cow::Owned(_) => unreachable!("lines are borrowed from the input"),
+        },
+    )(input)
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// continue-req = "+" SP [base64] CRLF
+///
+/// Note: Do not consume CRLF, because it is done in higher-level parser
+pub(crate) fn continue_req(input: &[u8]) -> IResult<&[u8], &str> {
+    preceded(tuple((tag("+"), SP)), base64)(input)
 }
 
 // -------------------------------------------------------------------------------------------------
@@ -323,27 +407,43 @@ pub(crate) fn dot_stuffed(input: &[u8]) -> IResult<&[u8], String> {
 /// capability = capa-tag *(SP param) CRLF
 ///
 /// Note: 512 octets maximum
-pub(crate) fn capability(input: &[u8]) -> IResult<&[u8], Capability> {
+///
+/// Note: Do not consume CRLF, because it is done in higher-level multi-line parser
+pub(crate) fn capability<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> IResult<&'a [u8], Capability<'a>> {
+    let line_ending = |input| options.line_ending(input);
+    let sp = |input| options.sp(input);
+
     let mut parser = alt((
-        value(Capability::Top, tuple((tag_no_case("TOP"), line_ending))),
-        value(Capability::User, tuple((tag_no_case("USER"), line_ending))),
+        value(
+            Capability::Top,
+            tuple((tag_no_case("TOP"), peek(line_ending))),
+        ),
+        value(
+            Capability::User,
+            tuple((tag_no_case("USER"), peek(line_ending))),
+        ),
         map(
-            tuple((tag_no_case("SASL"), many0(preceded(SP, param)), line_ending)),
-            |(_, params, _)| Capability::Sasl {
-                mechanisms: params.into_iter().map(ToOwned::to_owned).collect(),
-            },
+            tuple((
+                tag_no_case("SASL"),
+                many0(preceded(sp, auth_type)),
+                peek(line_ending),
+            )),
+            |(_, mechanisms, _)| Capability::Sasl { mechanisms },
         ),
         value(
             Capability::RespCodes,
-            tuple((tag_no_case("RESP-CODES"), line_ending)),
+            tuple((tag_no_case("RESP-CODES"), peek(line_ending))),
         ),
         map(
             tuple((
                 tag_no_case("LOGIN-DELAY"),
-                SP,
+                sp,
                 number,
-                opt(tag_no_case(" USER")),
-                line_ending,
+                opt(preceded(sp, tag_no_case("USER"))),
+                peek(line_ending),
             )),
             |(_, _, minimum_seconds, differ_per_user, _)| Capability::LoginDelay {
                 minimum_seconds,
@@ -352,53 +452,66 @@ pub(crate) fn capability(input: &[u8]) -> IResult<&[u8], Capability> {
         ),
         value(
             Capability::Pipelining,
-            tuple((tag_no_case("PIPELINING"), line_ending)),
+            tuple((tag_no_case("PIPELINING"), peek(line_ending))),
         ),
         map(
             tuple((
                 tag_no_case("EXPIRE"),
-                SP,
+                sp,
                 alt((
                     map(number, ExpirePolicy::MinimumDays),
                     value(ExpirePolicy::Never, tag_no_case("NEVER")),
                 )),
-                opt(tag_no_case(" USER")),
-                line_ending,
+                opt(preceded(sp, tag_no_case("USER"))),
+                peek(line_ending),
             )),
             |(_, _, policy, per_user, _)| Capability::Expire {
                 policy,
                 per_user: per_user.is_some(),
             },
         ),
-        value(Capability::Uidl, tuple((tag_no_case("UIDL"), line_ending))),
+        value(
+            Capability::Uidl,
+            tuple((tag_no_case("UIDL"), peek(line_ending))),
+        ),
         map(
             tuple((
                 tag_no_case("IMPLEMENTATION"),
-                SP,
-                map_res(not_line_ending, from_utf8),
-                line_ending,
+                sp,
+                map_res(|input| options.not_line_ending(input), from_utf8),
+                peek(line_ending),
             )),
             |(_, _, tag, _)| Capability::Implementation {
-                text: tag.to_owned(),
+                text: Cow::Borrowed(tag),
             },
         ),
-        value(Capability::Stls, tuple((tag_no_case("STLS"), line_ending))),
+        value(
+            Capability::Stls,
+            tuple((tag_no_case("STLS"), peek(line_ending))),
+        ),
         value(
             Capability::AuthRespCode,
-            tuple((tag_no_case("AUTH-RESP-CODE"), line_ending)),
+            tuple((tag_no_case("AUTH-RESP-CODE"), peek(line_ending))),
+        ),
+        value(
+            Capability::Lang,
+            tuple((tag_no_case("LANG"), peek(line_ending))),
         ),
-        value(Capability::Lang, tuple((tag_no_case("LANG"), line_ending))),
         map(
-            tuple((tag_no_case("UTF8"), opt(tag_no_case(" USER")), line_ending)),
+            tuple((
+                tag_no_case("UTF8"),
+                opt(preceded(sp, tag_no_case("USER"))),
+                peek(line_ending),
+            )),
             |(_, in_credentials, _)| Capability::Utf8 {
                 in_credentials: in_credentials.is_some(),
             },
         ),
         map(
-            tuple((capa_tag, many0(preceded(SP, param)), line_ending)),
+            tuple((capa_tag, many0(preceded(sp, param)), peek(line_ending))),
             |(tag, params, _)| Capability::Other {
-                tag: tag.to_owned(),
-                parameters: params.into_iter().map(ToOwned::to_owned).collect(),
+                tag: Cow::Borrowed(tag),
+                parameters: params.into_iter().map(Cow::Borrowed).collect(),
             },
         ),
     ));
@@ -428,7 +541,10 @@ mod test {
     use super::*;
     use crate::{
         parse::greeting,
-        types::response::{Greeting, Response},
+        types::{
+            command::Mechanism,
+            response::{Greeting, Response},
+        },
     };
 
     #[test]
@@ -437,7 +553,7 @@ mod test {
             (
                 b"+OK\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                     timestamp: None,
                 },
@@ -445,7 +561,7 @@ mod test {
             (
                 b"+OK \r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                     timestamp: None,
                 },
@@ -453,7 +569,7 @@ mod test {
             (
                 b"+OK A\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "A".into(),
                     timestamp: None,
                 },
@@ -461,7 +577,7 @@ mod test {
             (
                 b"+OK Z\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Z".into(),
                     timestamp: None,
                 },
@@ -469,7 +585,7 @@ mod test {
             (
                 b"+ok Hello World!\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Hello World!".into(),
                     timestamp: None,
                 },
@@ -477,7 +593,7 @@ mod test {
             (
                 b"+ok Hello <123> World!\r\n",
                 Greeting {
-                    code: vec![],
+                    code: None,
                     comment: "Hello <> World!".into(),
                     timestamp: Some("123".into()),
                 },
@@ -485,7 +601,7 @@ mod test {
             (
                 b"+ok [a] Hello World!\r\n",
                 Greeting {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "Hello World!".into(),
                     timestamp: None,
                 },
@@ -493,7 +609,7 @@ mod test {
             (
                 b"+ok [a] Hello <123> World!\r\n",
                 Greeting {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "Hello <> World!".into(),
                     timestamp: Some("123".into()),
                 },
@@ -513,77 +629,78 @@ mod test {
             (
                 b"+OK\r\n",
                 Response::Ok(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                 }),
             ),
             (
                 b"+OK \r\n",
                 Response::Ok(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                 }),
             ),
             (
                 b"+OK ABC!\r\n",
                 Response::Ok(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "ABC!".into(),
                 }),
             ),
             (
                 b"+OK [a] ABC!\r\n",
                 Response::Ok(SingleLine {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "ABC!".into(),
                 }),
             ),
             (
                 b"+OK [a/b] ABC! 1 < 3\r\n",
                 Response::Ok(SingleLine {
-                    code: vec!["a".into(), "b".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into(), "b".into()])),
                     comment: "ABC! 1 < 3".into(),
                 }),
             ),
             (
                 b"-ERR\r\n",
                 Response::Err(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                 }),
             ),
             (
                 b"-Err \r\n",
                 Response::Err(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "".into(),
                 }),
             ),
             (
                 b"-ERR ABC!\r\n",
                 Response::Err(SingleLine {
-                    code: vec![],
+                    code: None,
                     comment: "ABC!".into(),
                 }),
             ),
             (
                 b"-eRr [a] ABC!\r\n",
                 Response::Err(SingleLine {
-                    code: vec!["a".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
                     comment: "ABC!".into(),
                 }),
             ),
             (
                 b"-eRR [a/b] ABC! 1 < 3\r\n",
                 Response::Err(SingleLine {
-                    code: vec!["a".into(), "b".into()],
+                    code: Some(ResponseCode::Other(vec!["a".into(), "b".into()])),
                     comment: "ABC! 1 < 3".into(),
                 }),
             ),
         ];
 
         for (test, expected) in tests {
-            let (rem, got) = single_line(test, head, false).unwrap();
+            let options = ParseOptions::default();
+            let (rem, got) = single_line(test, &options, |i| head(i, &options), false).unwrap();
             assert!(rem.is_empty());
             assert_eq!(*expected, got);
         }
@@ -591,21 +708,23 @@ mod test {
 
     #[test]
     fn test_multi_lines() {
-        let tests: &[(&[u8], Vec<String>)] = &[
+        let tests: &[(&[u8], Vec<&[u8]>)] = &[
             (b"+OK\r\n.\r\n", vec![]),
-            (b"+OK\r\n..\r\n.\r\n", vec!["..".into()]),
-            (
-                b"+OK\r\n...\r\n..\r\n.\r\n",
-                vec!["...".into(), "..".into()],
-            ),
-            (b"+OK\r\n\r\n.\r\n", vec!["".into()]),
-            (b"+OK\r\n \r\n.\r\n", vec![" ".into()]),
+            (b"+OK\r\n..\r\n.\r\n", vec![b"."]),
+            (b"+OK\r\n...\r\n..\r\n.\r\n", vec![b"..", b"."]),
+            (b"+OK\r\n..foo\r\n.\r\n", vec![b".foo"]),
+            (b"+OK\r\nfoo.\r\n.\r\n", vec![b"foo."]),
+            (b"+OK\r\n.foo\r\n.\r\n", vec![b"foo"]),
+            (b"+OK\r\n\r\n.\r\n", vec![b""]),
+            (b"+OK\r\n \r\n.\r\n", vec![b" "]),
+            (b"+OK\r\nGr\xfc\xdfe\r\n.\r\n", vec![b"Gr\xfc\xdfe"]),
         ];
 
         for (test, expected) in tests {
-            let (rem, got) = multi_line(test, dot_stuffed).unwrap();
+            let options = ParseOptions::strict();
+            let (rem, got) = multi_line(test, &options, |i| dot_stuffed(i, &options)).unwrap();
             assert!(rem.is_empty());
-            assert_eq!(*expected, got.unwrap().body);
+            assert_eq!(got.unwrap().body, *expected);
         }
     }
 
@@ -617,7 +736,7 @@ mod test {
             (
                 b"SASL AUTH PLAIN\r\n",
                 Capability::Sasl {
-                    mechanisms: vec!["AUTH".into(), "PLAIN".into()],
+                    mechanisms: vec!["AUTH".parse().unwrap(), Mechanism::Plain],
                 },
             ),
             (b"RESP-CODES\r\n", Capability::RespCodes),
@@ -725,7 +844,9 @@ mod test {
 
         for (test, expected) in tests {
             // Parse ...
-            let (rem, got) = capability(test).unwrap();
+            let options = ParseOptions::strict();
+            let (rem, got) =
+                terminated(|i| capability(i, &options), |i| options.line_ending(i))(test).unwrap();
             assert!(rem.is_empty());
             assert_eq!(*expected, got);
 
@@ -0,0 +1,794 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    server::{lines, AuthError, Authenticator, Maildrop},
+    session::{ServerEvent, ServerSession, SessionError},
+    types::{
+        Capability, Command, DropListing, Greeting, Language, LanguageListing, LanguageTag,
+        MessageNumber, MultiLine, Response, ResponseCode, ScanListing, SerializePayload,
+        SingleLine, UniqueIdListing,
+    },
+};
+
+/// Blocking POP3 server.
+///
+/// Every connection is handled by [handle](Server::handle), e.g., in its own thread.
+///
+/// ```rust,no_run
+/// use std::{net::TcpListener, sync::Arc, thread};
+///
+/// use pop3_codec::server::{AuthError, Authenticator, Maildrop, Server};
+///
+/// # struct Empty;
+/// # impl Maildrop for Empty {
+/// #     fn len(&self) -> u32 { 0 }
+/// #     fn size(&self, _: u32) -> u64 { 0 }
+/// #     fn uid(&self, _: u32) -> String { unreachable!() }
+/// #     fn fetch(&mut self, _: u32) -> std::io::Result<Vec<u8>> { unreachable!() }
+/// #     fn commit(&mut self, _: &[u32]) -> std::io::Result<()> { Ok(()) }
+/// # }
+/// struct Users;
+///
+/// impl Authenticator for Users {
+///     type Maildrop = Empty;
+///
+///     fn login(&self, user: &str, password: &str) -> Result<Empty, AuthError> {
+///         match (user, password) {
+///             ("alice", "secret") => Ok(Empty),
+///             _ => Err(AuthError::Invalid),
+///         }
+///     }
+/// }
+///
+/// let server = Arc::new(Server::new(Users));
+/// let listener = TcpListener::bind("127.0.0.1:1110").unwrap();
+///
+/// for stream in listener.incoming() {
+///     let server = server.clone();
+///     let stream = stream.unwrap();
+///
+///     thread::spawn(move || server.handle(stream));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Server<A> {
+    authenticator: A,
+    hostname: String,
+    /// Languages offered with LANG (RFC 6856). The first one is the default.
+    languages: Vec<LanguageListing<'static>>,
+    /// Used to make the APOP timestamps unique.
+    counter: AtomicU64,
+}
+
+impl<A> Server<A>
+where
+    A: Authenticator,
+{
+    pub fn new(authenticator: A) -> Self {
+        Self {
+            authenticator,
+            hostname: "localhost".into(),
+            languages: Vec::new(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the hostname used in the APOP timestamp of the greeting.
+    pub fn with_hostname(mut self, hostname: &str) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// Offers `languages` with LANG (RFC 6856), where the first language is the default.
+    ///
+    /// The client selects a language using "lookup" (RFC 4647), see [Language::lookup]. Note:
+    /// Only the negotiation is implemented, i.e., responses are not translated.
+    pub fn with_languages(mut self, languages: Vec<LanguageListing<'static>>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// Handles a single connection until the client sent QUIT or closed the connection.
+    ///
+    /// Messages are only deleted, when the client sent QUIT.
+    ///
+    /// Returns an error with [ErrorKind::InvalidData], when the session gets into an unexpected
+    /// state, instead of panicking.
+    pub fn handle<S>(&self, mut stream: S) -> std::io::Result<()>
+    where
+        S: Read + Write,
+    {
+        let timestamp = format!(
+            "{}.{}.{}@{}",
+            std::process::id(),
+            self.counter.fetch_add(1, Ordering::Relaxed),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            self.hostname,
+        );
+
+        let mut connection = Connection {
+            authenticator: &self.authenticator,
+            languages: &self.languages,
+            session: ServerSession::new(),
+            timestamp,
+            user: None,
+            maildrop: None,
+            deleted: Vec::new(),
+        };
+
+        let greeting = connection.session.greeting(Greeting {
+            code: None,
+            comment: "POP3 server ready <>".into(),
+            timestamp: Some(connection.timestamp.as_str().into()),
+        });
+        stream.write_all(&greeting)?;
+        stream.flush()?;
+
+        let mut buffer = [0; 1024];
+
+        while !connection.session.is_closed() {
+            let bytes = match connection.session.next_event() {
+                Ok(Some(ServerEvent::Command(command))) => connection.execute(command)?,
+                Ok(Some(ServerEvent::Rejected { response, .. })) => response,
+                // No challenge is ever sent, because AUTH is not supported.
+                Ok(Some(ServerEvent::AuthData(_))) => {
+                    return Err(invalid("unexpected authentication data"))
+                }
+                Ok(None) => {
+                    match stream.read(&mut buffer)? {
+                        0 => return Ok(()),
+                        n => connection.session.receive(&buffer[..n]),
+                    }
+                    continue;
+                }
+                Err(SessionError::Closed) => break,
+                Err(error) => return Err(std::io::Error::new(ErrorKind::InvalidData, error)),
+            };
+
+            stream.write_all(&bytes)?;
+            stream.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// State of a single connection.
+struct Connection<'a, A>
+where
+    A: Authenticator,
+{
+    authenticator: &'a A,
+    languages: &'a [LanguageListing<'static>],
+    session: ServerSession,
+    timestamp: String,
+    /// The name sent with USER.
+    user: Option<String>,
+    /// The maildrop is opened in the TRANSACTION state.
+    maildrop: Option<A::Maildrop>,
+    /// Messages marked as deleted (sorted).
+    deleted: Vec<u32>,
+}
+
+impl<'a, A> Connection<'a, A>
+where
+    A: Authenticator,
+{
+    /// Executes `command` and returns the serialized response.
+    fn execute(&mut self, command: Command<'_>) -> std::io::Result<Vec<u8>> {
+        match command {
+            Command::User(name) => {
+                self.user = Some(name.into_owned());
+                self.ok(SingleLine {
+                    code: None,
+                    comment: "send PASS".into(),
+                })
+            }
+            Command::Pass(password) => {
+                let user = self.user.take().unwrap_or_default();
+                let result = self.authenticator.login(&user, &password);
+                self.open(result)
+            }
+            Command::Apop { name, digest } => {
+                let result = self.authenticator.apop(&name, &self.timestamp, &digest);
+                self.open(result)
+            }
+            Command::Capa => {
+                let mut body = vec![
+                    Capability::Top,
+                    Capability::User,
+                    Capability::Uidl,
+                    Capability::RespCodes,
+                    Capability::Pipelining,
+                ];
+
+                if !self.languages.is_empty() {
+                    body.push(Capability::Lang);
+                }
+
+                self.ok(MultiLine {
+                    head: line("capability list follows"),
+                    body,
+                })
+            }
+            Command::Stat => {
+                let listing = self.drop_listing()?;
+                self.ok(listing)
+            }
+            Command::ListAll => {
+                let maildrop = self.maildrop()?;
+                let body = self
+                    .messages()?
+                    .map(|msg| ScanListing {
+                        message_id: msg,
+                        message_size: maildrop.size(msg.get()),
+                        trailing: None,
+                    })
+                    .collect();
+                let DropListing {
+                    message_count,
+                    maildrop_size,
+                    ..
+                } = self.drop_listing()?;
+
+                self.ok(MultiLine {
+                    head: line(&format!(
+                        "{} messages ({} octets)",
+                        message_count, maildrop_size
+                    )),
+                    body,
+                })
+            }
+            Command::List { msg } => match self.check(msg)? {
+                Ok(()) => {
+                    let listing = ScanListing {
+                        message_id: msg,
+                        message_size: self.maildrop()?.size(msg.get()),
+                        trailing: None,
+                    };
+                    self.ok(listing)
+                }
+                Err(error) => self.fail(error),
+            },
+            Command::UidlAll => {
+                let maildrop = self.maildrop()?;
+                let body = self
+                    .messages()?
+                    .map(|msg| UniqueIdListing {
+                        message_id: msg,
+                        message_uid: maildrop.uid(msg.get()).into(),
+                        trailing: None,
+                    })
+                    .collect();
+
+                self.ok(MultiLine {
+                    head: line(""),
+                    body,
+                })
+            }
+            Command::Uidl { msg } => match self.check(msg)? {
+                Ok(()) => {
+                    let listing = UniqueIdListing {
+                        message_id: msg,
+                        message_uid: self.maildrop()?.uid(msg.get()).into(),
+                        trailing: None,
+                    };
+                    self.ok(listing)
+                }
+                Err(error) => self.fail(error),
+            },
+            Command::Retr { msg } => self.fetch(msg, None),
+            Command::Top { msg, n } => self.fetch(msg, Some(n)),
+            Command::Dele { msg } => match self.check(msg)? {
+                Ok(()) => {
+                    let index = self.deleted.binary_search(&msg.get()).unwrap_err();
+                    self.deleted.insert(index, msg.get());
+                    self.ok(line(&format!("message {} deleted", msg)))
+                }
+                Err(error) => self.fail(error),
+            },
+            Command::Noop => self.ok(line("")),
+            Command::Rset => {
+                self.deleted.clear();
+                let DropListing {
+                    message_count,
+                    maildrop_size,
+                    ..
+                } = self.drop_listing()?;
+
+                self.ok(line(&format!(
+                    "maildrop has {} messages ({} octets)",
+                    message_count, maildrop_size
+                )))
+            }
+            Command::Quit => self.quit(),
+            Command::LangAll if !self.languages.is_empty() => self.ok(MultiLine {
+                head: line("language listing follows"),
+                body: self.languages.to_vec(),
+            }),
+            Command::Lang { lang_or_wild } if !self.languages.is_empty() => {
+                self.lang(&lang_or_wild)
+            }
+            Command::Stls
+            | Command::AuthAll
+            | Command::Auth { .. }
+            | Command::Utf8
+            | Command::LangAll
+            | Command::Lang { .. } => self.err(None, "command not supported"),
+        }
+    }
+
+    /// Answers PASS or APOP.
+    fn open(&mut self, result: Result<A::Maildrop, AuthError>) -> std::io::Result<Vec<u8>> {
+        match result {
+            Ok(maildrop) => {
+                self.maildrop = Some(maildrop);
+                let DropListing {
+                    message_count,
+                    maildrop_size,
+                    ..
+                } = self.drop_listing()?;
+
+                self.ok(line(&format!(
+                    "maildrop has {} messages ({} octets)",
+                    message_count, maildrop_size
+                )))
+            }
+            Err(AuthError::Invalid) => self.err(Some(ResponseCode::Auth), "invalid credentials"),
+            Err(AuthError::Unsupported) => self.err(None, "authentication method not supported"),
+            Err(AuthError::InUse) => self.err(Some(ResponseCode::InUse), "maildrop already locked"),
+            Err(AuthError::Io(_)) => {
+                self.err(Some(ResponseCode::SysTemp), "unable to open maildrop")
+            }
+        }
+    }
+
+    /// Answers RETR (when `n` is `None`) or TOP.
+    fn fetch(&mut self, msg: MessageNumber, n: Option<u32>) -> std::io::Result<Vec<u8>> {
+        if let Err(error) = self.check(msg)? {
+            return self.fail(error);
+        }
+
+        let message = match self.maildrop_mut()?.fetch(msg.get()) {
+            Ok(message) => message,
+            Err(_) => return self.err(Some(ResponseCode::SysTemp), "unable to read message"),
+        };
+
+        let mut body = lines(&message);
+
+        if let Some(n) = n {
+            // The header ends with the first empty line.
+            let header = body
+                .iter()
+                .position(|line| line.is_empty())
+                .unwrap_or(body.len());
+            body.truncate((header + 1).saturating_add(n as usize).min(body.len()));
+        }
+
+        let head = match n {
+            Some(_) => line(""),
+            None => line(&format!("{} octets", self.maildrop()?.size(msg.get()))),
+        };
+
+        self.ok(MultiLine { head, body })
+    }
+
+    /// Answers LANG with a parameter, i.e., selects the best matching language ("*" selects the
+    /// default).
+    fn lang(&mut self, range: &Language) -> std::io::Result<Vec<u8>> {
+        let tags: Vec<LanguageTag> = self
+            .languages
+            .iter()
+            .map(|language| language.tag.clone())
+            .collect();
+
+        let selected = match range {
+            Language::Wild => tags.first(),
+            Language::Lang(_) => range.lookup(&tags),
+        };
+
+        match selected {
+            Some(tag) => {
+                let comment = format!("{} language changed", tag);
+                self.ok(line(&comment))
+            }
+            None => self.err(None, &format!("invalid language {}", range)),
+        }
+    }
+
+    /// Answers QUIT, i.e., enters the UPDATE state and removes deleted messages.
+    fn quit(&mut self) -> std::io::Result<Vec<u8>> {
+        let deleted = std::mem::take(&mut self.deleted);
+
+        match self
+            .maildrop
+            .as_mut()
+            .map(|maildrop| maildrop.commit(&deleted))
+        {
+            Some(Err(_)) => self.err(
+                Some(ResponseCode::SysTemp),
+                "some deleted messages not removed",
+            ),
+            _ => self.ok(line("POP3 server signing off")),
+        }
+    }
+
+    /// Checks that `msg` exists and is not marked as deleted.
+    ///
+    /// The inner error is the response to the client.
+    fn check(&self, msg: MessageNumber) -> std::io::Result<Result<(), SingleLine<'static>>> {
+        if msg.get() > self.maildrop()?.len() {
+            return Ok(Err(line("no such message")));
+        }
+
+        if self.deleted.binary_search(&msg.get()).is_ok() {
+            return Ok(Err(line(&format!("message {} already deleted", msg))));
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Returns the messages, which are not marked as deleted.
+    fn messages(&self) -> std::io::Result<impl Iterator<Item = MessageNumber> + '_> {
+        Ok((1..=self.maildrop()?.len())
+            .filter(move |msg| self.deleted.binary_search(msg).is_err())
+            .filter_map(MessageNumber::new))
+    }
+
+    fn drop_listing(&self) -> std::io::Result<DropListing<'static>> {
+        let mut listing = DropListing {
+            message_count: 0,
+            maildrop_size: 0,
+            trailing: None,
+        };
+
+        for msg in self.messages()? {
+            listing.message_count += 1;
+            listing.maildrop_size += self.maildrop()?.size(msg.get());
+        }
+
+        Ok(listing)
+    }
+
+    /// Returns the maildrop.
+    ///
+    /// The session ensures, that transaction commands are only executed after authentication.
+    fn maildrop(&self) -> std::io::Result<&A::Maildrop> {
+        self.maildrop
+            .as_ref()
+            .ok_or_else(|| invalid("maildrop is not open"))
+    }
+
+    fn maildrop_mut(&mut self) -> std::io::Result<&mut A::Maildrop> {
+        self.maildrop
+            .as_mut()
+            .ok_or_else(|| invalid("maildrop is not open"))
+    }
+
+    fn ok<O>(&mut self, payload: O) -> std::io::Result<Vec<u8>>
+    where
+        O: std::fmt::Debug + Clone + PartialEq + Eq + SerializePayload,
+    {
+        self.respond(Response::Ok(payload))
+    }
+
+    fn err(&mut self, code: Option<ResponseCode>, comment: &str) -> std::io::Result<Vec<u8>> {
+        self.fail(SingleLine {
+            code,
+            comment: comment.into(),
+        })
+    }
+
+    fn fail(&mut self, error: SingleLine<'_>) -> std::io::Result<Vec<u8>> {
+        self.respond(Response::<SingleLine, SingleLine>::Err(error))
+    }
+
+    fn respond<O>(&mut self, response: Response<O, SingleLine<'_>>) -> std::io::Result<Vec<u8>>
+    where
+        O: std::fmt::Debug + Clone + PartialEq + Eq + SerializePayload,
+    {
+        // Every command is answered exactly once.
+        self.session
+            .respond(response)
+            .map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))
+    }
+}
+
+/// Error returned when the session gets into an unexpected state.
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn line(comment: &str) -> SingleLine<'static> {
+    SingleLine {
+        code: None,
+        comment: comment.to_owned().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::*;
+    use crate::{client::BlockingClient, types::IntoOwned};
+
+    /// Messages passed to [Maildrop::commit] (if any).
+    type Committed = Arc<Mutex<Option<Vec<u32>>>>;
+
+    struct TestMaildrop {
+        messages: Vec<&'static [u8]>,
+        committed: Committed,
+    }
+
+    impl Maildrop for TestMaildrop {
+        fn len(&self) -> u32 {
+            self.messages.len() as u32
+        }
+
+        fn size(&self, msg: u32) -> u64 {
+            self.messages[msg as usize - 1].len() as u64
+        }
+
+        fn uid(&self, msg: u32) -> String {
+            format!("uid{}", msg)
+        }
+
+        fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>> {
+            Ok(self.messages[msg as usize - 1].to_vec())
+        }
+
+        fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()> {
+            *self.committed.lock().unwrap() = Some(deleted.to_vec());
+            Ok(())
+        }
+    }
+
+    struct TestAuthenticator {
+        committed: Committed,
+    }
+
+    impl Authenticator for TestAuthenticator {
+        type Maildrop = TestMaildrop;
+
+        fn login(&self, user: &str, password: &str) -> Result<TestMaildrop, AuthError> {
+            match (user, password) {
+                ("mrose", "secret") => Ok(TestMaildrop {
+                    messages: vec![
+                        b"Subject: One\r\n\r\nLine 1\r\nLine 2\r\n",
+                        b"Subject: Two\r\n\r\n.\r\n",
+                    ],
+                    committed: self.committed.clone(),
+                }),
+                ("locked", _) => Err(AuthError::InUse),
+                _ => Err(AuthError::Invalid),
+            }
+        }
+    }
+
+    /// Starts a server for a single connection and returns a connected client.
+    fn start(
+        languages: Vec<LanguageListing<'static>>,
+    ) -> (
+        BlockingClient<crate::client::TcpConnection>,
+        thread::JoinHandle<()>,
+        Committed,
+    ) {
+        let committed = Arc::new(Mutex::new(None));
+        let server = Server::new(TestAuthenticator {
+            committed: committed.clone(),
+        })
+        .with_languages(languages);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            server.handle(stream).unwrap();
+        });
+
+        let client = BlockingClient::connect_tcp(addr, None).unwrap();
+
+        (client, handle, committed)
+    }
+
+    #[test]
+    fn test_server() {
+        let (mut client, handle, committed) = start(vec![]);
+        assert!(client.greeting().timestamp.is_some());
+
+        // Not valid in the AUTHORIZATION state.
+        assert!(matches!(client.stat(), Ok(Response::Err(_))));
+
+        client.user("mrose").unwrap().unwrap();
+        match client.pass("wrong").unwrap() {
+            Response::Err(error) => assert_eq!(error.code, Some(ResponseCode::Auth)),
+            Response::Ok(_) => panic!(),
+        }
+        client.user("mrose").unwrap().unwrap();
+        client.pass("secret").unwrap().unwrap();
+
+        assert_eq!(
+            client.stat().unwrap().unwrap(),
+            DropListing {
+                message_count: 2,
+                maildrop_size: 51,
+                trailing: None,
+            }
+        );
+        assert_eq!(
+            client
+                .uidl(MessageNumber::new(2).unwrap())
+                .unwrap()
+                .unwrap()
+                .message_uid,
+            "uid2".to_owned()
+        );
+        assert_eq!(
+            client
+                .retr(MessageNumber::new(2).unwrap())
+                .unwrap()
+                .unwrap()
+                .body_bytes(),
+            b"Subject: Two\r\n\r\n.\r\n"
+        );
+        assert_eq!(
+            client
+                .top(MessageNumber::new(1).unwrap(), 1)
+                .unwrap()
+                .unwrap()
+                .body_bytes(),
+            b"Subject: One\r\n\r\nLine 1\r\n"
+        );
+        assert!(matches!(
+            client.list(MessageNumber::new(3).unwrap()),
+            Ok(Response::Err(_))
+        ));
+
+        // RSET unmarks deleted messages.
+        client
+            .dele(MessageNumber::new(1).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            client.dele(MessageNumber::new(1).unwrap()),
+            Ok(Response::Err(_))
+        ));
+        assert!(matches!(
+            client.retr(MessageNumber::new(1).unwrap()),
+            Ok(Response::Err(_))
+        ));
+        assert_eq!(client.list_all().unwrap().unwrap().body.len(), 1);
+        client.rset().unwrap().unwrap();
+        assert_eq!(client.list_all().unwrap().unwrap().body.len(), 2);
+
+        client
+            .dele(MessageNumber::new(2).unwrap())
+            .unwrap()
+            .unwrap();
+        client.quit().unwrap().unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(*committed.lock().unwrap(), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_server_connection_lost() {
+        let (mut client, handle, committed) = start(vec![]);
+
+        client.user("mrose").unwrap().unwrap();
+        client.pass("secret").unwrap().unwrap();
+        client
+            .dele(MessageNumber::new(1).unwrap())
+            .unwrap()
+            .unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        // Nothing is deleted without QUIT.
+        assert_eq!(*committed.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn test_server_in_use() {
+        let (mut client, handle, _) = start(vec![]);
+
+        client.user("locked").unwrap().unwrap();
+        match client.pass("secret").unwrap() {
+            Response::Err(error) => assert_eq!(error.code, Some(ResponseCode::InUse)),
+            Response::Ok(_) => panic!(),
+        }
+        client.quit().unwrap().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_server_lang() {
+        let listing = |tag: &str, description: &'static str| LanguageListing {
+            tag: tag.parse().unwrap(),
+            description: description.into(),
+        };
+        let (mut client, handle, _) = start(vec![
+            listing("en", "English"),
+            listing("de", "Deutsch"),
+            listing("de-CH", "Schweizerdeutsch"),
+        ]);
+
+        assert!(client
+            .capa()
+            .unwrap()
+            .unwrap()
+            .body
+            .contains(&Capability::Lang));
+        assert_eq!(
+            client
+                .execute(&Command::LangAll, |input| {
+                    crate::parse::response_lang_all(input)
+                        .map(|(rem, response)| (rem, response.into_owned()))
+                })
+                .unwrap()
+                .unwrap()
+                .body
+                .len(),
+            3
+        );
+
+        let mut lang = |range: &str| {
+            let lang_or_wild = match range {
+                "*" => Language::Wild,
+                range => Language::Lang(range.parse().unwrap()),
+            };
+
+            client
+                .execute(&Command::Lang { lang_or_wild }, |input| {
+                    crate::parse::response_lang(input)
+                        .map(|(rem, response)| (rem, response.into_owned()))
+                })
+                .unwrap()
+        };
+        assert_eq!(
+            lang("de-CH-1996").unwrap().comment,
+            "de-CH language changed"
+        );
+        assert_eq!(lang("DE-AT").unwrap().comment, "de language changed");
+        assert_eq!(lang("*").unwrap().comment, "en language changed");
+        assert!(matches!(lang("fr"), Response::Err(_)));
+
+        client.quit().unwrap().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_state() {
+        let authenticator = TestAuthenticator {
+            committed: Arc::new(Mutex::new(None)),
+        };
+        let mut connection = Connection {
+            authenticator: &authenticator,
+            languages: &[],
+            session: ServerSession::new(),
+            timestamp: "".into(),
+            user: None,
+            maildrop: None,
+            deleted: Vec::new(),
+        };
+
+        // Neither a maildrop is open nor is a command waiting for a response.
+        let error = connection.execute(Command::Stat).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let error = connection.execute(Command::Noop).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::server::{message_size, stable_hash, Maildrop};
+
+/// Separates the unique part of a Maildir filename from the flags.
+///
+/// Note: ":" is not allowed in filenames on Windows, where "!" is commonly used instead.
+#[cfg(unix)]
+const SEPARATOR: char = ':';
+#[cfg(not(unix))]
+const SEPARATOR: char = '!';
+
+/// Maildrop backed by a Maildir directory.
+///
+/// Messages in `new/` and `cur/` are served ordered by their filename, i.e., by delivery time for
+/// common naming schemes. The unique-id is derived from the unique part of the filename, which
+/// does not change when a message is moved or its flags are changed.
+///
+/// On QUIT, deleted messages are removed and the remaining messages in `new/` are moved to `cur/`
+/// without any flags, i.e., they are not marked as seen (other clients may still show them as
+/// unread). Both operations are atomic for every single file.
+#[derive(Debug)]
+pub struct MaildirMaildrop {
+    root: PathBuf,
+    messages: Vec<Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Path relative to the root, e.g., "new/1234.abc.host".
+    path: PathBuf,
+    size: u64,
+    uid: String,
+}
+
+impl MaildirMaildrop {
+    /// Opens the Maildir at `root` and reads the sizes of all messages.
+    pub fn open<P>(root: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref().to_path_buf();
+        let mut messages = Vec::new();
+
+        for subdirectory in ["new", "cur"] {
+            for entry in fs::read_dir(root.join(subdirectory))? {
+                let entry = entry?;
+                let name = entry.file_name();
+
+                let name = match name.to_str() {
+                    Some(name) if !name.starts_with('.') && entry.file_type()?.is_file() => name,
+                    _ => continue,
+                };
+
+                let message = fs::read(entry.path())?;
+
+                messages.push(Entry {
+                    path: Path::new(subdirectory).join(name),
+                    size: message_size(&message),
+                    uid: uid(name),
+                });
+            }
+        }
+
+        messages.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name()));
+
+        Ok(Self { root, messages })
+    }
+
+    fn entry(&self, msg: u32) -> &Entry {
+        &self.messages[msg as usize - 1]
+    }
+}
+
+impl Maildrop for MaildirMaildrop {
+    fn len(&self) -> u32 {
+        self.messages.len() as u32
+    }
+
+    fn size(&self, msg: u32) -> u64 {
+        self.entry(msg).size
+    }
+
+    fn uid(&self, msg: u32) -> String {
+        self.entry(msg).uid.clone()
+    }
+
+    fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>> {
+        fs::read(self.root.join(&self.entry(msg).path))
+    }
+
+    fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()> {
+        for (index, entry) in self.messages.iter().enumerate() {
+            let path = self.root.join(&entry.path);
+
+            let result = if deleted.binary_search(&(index as u32 + 1)).is_ok() {
+                fs::remove_file(&path)
+            } else if entry.path.starts_with("new") {
+                let name = entry.path.file_name().unwrap().to_string_lossy();
+                let name = format!("{}{}2,", name, SEPARATOR);
+                fs::rename(&path, self.root.join("cur").join(name))
+            } else {
+                continue;
+            };
+
+            match result {
+                // Someone else (e.g., another client) removed the message in the meantime.
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Derives the unique-id from the unique part of a Maildir filename, i.e., without flags.
+///
+/// The unique part is used as is when it is a valid unique-id (RFC 1939). Otherwise, it is hashed.
+fn uid(name: &str) -> String {
+    let unique = match name.split_once(SEPARATOR) {
+        Some((unique, _)) => unique,
+        None => name,
+    };
+
+    if !unique.is_empty()
+        && unique.len() <= 70
+        && unique.bytes().all(|b| (0x21..=0x7e).contains(&b))
+    {
+        unique.into()
+    } else {
+        format!("{:016x}", stable_hash(unique.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates a Maildir in the temporary directory.
+    fn maildir(name: &str, messages: &[(&str, &[u8])]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "pop3-codec-maildir-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+
+        for subdirectory in ["new", "cur", "tmp"] {
+            fs::create_dir_all(root.join(subdirectory)).unwrap();
+        }
+
+        for (path, message) in messages {
+            fs::write(root.join(path), message).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_maildir() {
+        let seen = format!("cur/1000.M1P1.host{}2,S", SEPARATOR);
+        let root = maildir(
+            "test",
+            &[
+                (&seen, b"Subject: One\n\nHello\n"),
+                ("new/2000.M2P2.host", b"Subject: Two\r\n\r\nWorld\r\n"),
+                ("new/3000.M3P3.host", b"Subject: Three\n"),
+                ("tmp/4000.M4P4.host", b"Not delivered yet\n"),
+            ],
+        );
+
+        let mut maildrop = MaildirMaildrop::open(&root).unwrap();
+        assert_eq!(maildrop.len(), 3);
+        assert_eq!(maildrop.size(1), 23);
+        assert_eq!(maildrop.size(2), 23);
+        assert_eq!(maildrop.uid(1), "1000.M1P1.host");
+        assert_eq!(maildrop.uid(2), "2000.M2P2.host");
+        assert_eq!(maildrop.fetch(3).unwrap(), b"Subject: Three\n");
+
+        maildrop.commit(&[2]).unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(root.join("cur"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                format!("1000.M1P1.host{}2,S", SEPARATOR),
+                format!("3000.M3P3.host{}2,", SEPARATOR),
+            ]
+        );
+        assert_eq!(fs::read_dir(root.join("new")).unwrap().count(), 0);
+
+        // The unique-ids are stable.
+        let maildrop = MaildirMaildrop::open(&root).unwrap();
+        assert_eq!(maildrop.len(), 2);
+        assert_eq!(maildrop.uid(2), "3000.M3P3.host");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_uid() {
+        assert_eq!(
+            uid(&format!("1000.M1P1.host{}2,RS", SEPARATOR)),
+            "1000.M1P1.host"
+        );
+        assert_eq!(uid("1000.M1P1.host"), "1000.M1P1.host");
+        assert_eq!(
+            uid("with space"),
+            format!("{:016x}", stable_hash(b"with space"))
+        );
+        assert_eq!(uid(&"a".repeat(71)).len(), 16);
+    }
+}
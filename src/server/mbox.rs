@@ -0,0 +1,550 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::server::{message_size, stable_hash, Maildrop, MaildropLocked};
+
+/// Dotlocks older than this are considered stale, i.e., left behind by a crashed process.
+const STALE_DOTLOCK: Duration = Duration::from_secs(300);
+
+/// Interval, in which a held dotlock is refreshed, so that it never becomes stale.
+const REFRESH_DOTLOCK: Duration = Duration::from_secs(60);
+
+/// Maildrop backed by an mbox file.
+///
+/// Messages are separated by "From " lines at the start of the file or after an empty line.
+/// Quoted lines (">From ", ">>From ", ...) are unquoted once (mboxrd). The unique-id is derived
+/// from a hash of the (unquoted) message, i.e., it does not depend on other messages.
+///
+/// The file is locked using a dotlock (`<path>.lock`) and, on Unix, an fcntl lock until the
+/// maildrop is dropped. The dotlock is refreshed while it is held, so that it is not mistaken for
+/// a stale lock by other processes. On QUIT, the file is rewritten in place (under the locks)
+/// without the deleted messages, i.e., its owner, permissions, and ACLs are kept. Data before the
+/// first message and data appended after the maildrop was opened are preserved.
+#[derive(Debug)]
+pub struct MboxMaildrop {
+    file: File,
+    /// Length of the data before the first message, e.g., garbage left by a broken MDA.
+    preamble: u64,
+    /// Length of the file when it was opened.
+    length: u64,
+    messages: Vec<Entry>,
+    _dotlock: Dotlock,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Range of the message in the file (including the "From " line).
+    range: Range<u64>,
+    size: u64,
+    uid: String,
+}
+
+impl MboxMaildrop {
+    /// Locks and opens the mbox file at `path`.
+    ///
+    /// Returns [MaildropLocked] (wrapped in a [std::io::Error]), when the file is locked by
+    /// another process. It is converted into [AuthError::InUse](crate::server::AuthError::InUse)
+    /// by `?`.
+    pub fn open<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        // When the fcntl lock can not be acquired, the dotlock is dropped, i.e., only the lock
+        // file created here is removed.
+        let dotlock = Dotlock::acquire(path)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        lock(&file)?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let ranges = split(&data);
+        let preamble = ranges.first().map_or(data.len(), |range| range.start);
+        let mut messages = Vec::new();
+        let mut hashes = HashMap::new();
+
+        for range in ranges {
+            let content = content(&data[range.clone()]);
+            let hash = stable_hash(&content);
+
+            // Identical messages can only be distinguished by their position among each other.
+            // Which one of them is deleted makes no difference to the client.
+            let duplicates = hashes.entry(hash).or_insert(0);
+            let uid = match *duplicates {
+                0 => format!("{:016x}", hash),
+                n => format!("{:016x}.{}", hash, n),
+            };
+            *duplicates += 1;
+
+            messages.push(Entry {
+                range: range.start as u64..range.end as u64,
+                size: message_size(&content),
+                uid,
+            });
+        }
+
+        Ok(Self {
+            file,
+            preamble: preamble as u64,
+            length: data.len() as u64,
+            messages,
+            _dotlock: dotlock,
+        })
+    }
+
+    fn entry(&self, msg: u32) -> &Entry {
+        &self.messages[msg as usize - 1]
+    }
+
+    fn read(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+        let mut data = vec![0; (range.end - range.start) as usize];
+
+        self.file.seek(SeekFrom::Start(range.start))?;
+        self.file.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+impl Maildrop for MboxMaildrop {
+    fn len(&self) -> u32 {
+        self.messages.len() as u32
+    }
+
+    fn size(&self, msg: u32) -> u64 {
+        self.entry(msg).size
+    }
+
+    fn uid(&self, msg: u32) -> String {
+        self.entry(msg).uid.clone()
+    }
+
+    fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>> {
+        let range = self.entry(msg).range.clone();
+
+        Ok(content(&self.read(range)?))
+    }
+
+    fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()> {
+        if deleted.is_empty() {
+            return Ok(());
+        }
+
+        let mut data = self.read(0..self.preamble)?;
+
+        for index in 0..self.messages.len() {
+            if deleted.binary_search(&(index as u32 + 1)).is_err() {
+                let range = self.messages[index].range.clone();
+                data.extend_from_slice(&self.read(range)?);
+            }
+        }
+
+        // Keep messages, which were delivered while the maildrop was open.
+        let end = self.file.metadata()?.len();
+        data.extend_from_slice(&self.read(self.length..end)?);
+
+        // The file is rewritten in place (instead of being replaced), because an MDA, which only
+        // uses fcntl locking, would otherwise append to the old file once the locks are released.
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&data)?;
+        self.file.set_len(data.len() as u64)?;
+        self.file.sync_all()?;
+
+        self.length = data.len() as u64;
+        self.messages.clear();
+
+        Ok(())
+    }
+}
+
+/// Lock file, which is refreshed by a background thread while held and removed when dropped.
+///
+/// The file contains a token (the process id and a nonce), so that a lock file, which was
+/// replaced by another process, is neither refreshed nor removed.
+#[derive(Debug)]
+struct Dotlock {
+    path: PathBuf,
+    token: Vec<u8>,
+    /// Stops the refresh thread when dropped.
+    stop: Option<mpsc::Sender<()>>,
+    refresher: Option<JoinHandle<()>>,
+}
+
+impl Dotlock {
+    fn acquire(mbox: &Path) -> std::io::Result<Self> {
+        let mut path = mbox.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let token = token();
+
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    if let Err(error) = file.write_all(&token) {
+                        let _ = fs::remove_file(&path);
+                        return Err(error);
+                    }
+
+                    return Ok(Self::hold(path, token));
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    let owner = match fs::read(&path) {
+                        Ok(owner) => owner,
+                        // Released in the meantime.
+                        Err(error) if error.kind() == ErrorKind::NotFound => continue,
+                        Err(error) => return Err(error),
+                    };
+                    let modified = fs::metadata(&path)?.modified()?;
+                    let age = SystemTime::now()
+                        .duration_since(modified)
+                        .unwrap_or_default();
+
+                    if age < STALE_DOTLOCK {
+                        break;
+                    }
+
+                    // Remove the stale lock (unless it was replaced in the meantime) and try again.
+                    if fs::read(&path).ok().as_ref() == Some(&owner) {
+                        match fs::remove_file(&path) {
+                            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+                            _ => {}
+                        }
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Err(MaildropLocked.into_io())
+    }
+
+    /// Starts refreshing the lock file at `path`, which contains `token`.
+    fn hold(path: PathBuf, token: Vec<u8>) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let refresher = {
+            let path = path.clone();
+            let token = token.clone();
+
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(REFRESH_DOTLOCK) {
+                    if refresh(&path, &token).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+
+        Self {
+            path,
+            token,
+            stop: Some(stop),
+            refresher: Some(refresher),
+        }
+    }
+}
+
+impl Drop for Dotlock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+
+        if let Some(refresher) = self.refresher.take() {
+            let _ = refresher.join();
+        }
+
+        if fs::read(&self.path).ok().as_ref() == Some(&self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Rewrites the lock file at `path` (which updates its modification time) when it still contains
+/// `token`.
+fn refresh(path: &Path, token: &[u8]) -> std::io::Result<()> {
+    if fs::read(path)? != token {
+        return Err(MaildropLocked.into_io());
+    }
+
+    fs::write(path, token)
+}
+
+/// Returns a token, which identifies a lock file created by this process.
+fn token() -> Vec<u8> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    format!(
+        "{} {:x}.{}\n",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+    .into_bytes()
+}
+
+/// Acquires an fcntl write lock, which is released when the file is closed.
+#[cfg(unix)]
+fn lock(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: `flock` is a plain C struct, for which all zeroes is a valid value.
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = libc::F_WRLCK as _;
+    flock.l_whence = libc::SEEK_SET as _;
+
+    // Safety: The file descriptor is owned by `file`, which outlives the call.
+    match unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &flock) } {
+        -1 => {
+            let error = std::io::Error::last_os_error();
+
+            match error.raw_os_error() {
+                Some(libc::EACCES) | Some(libc::EAGAIN) => Err(MaildropLocked.into_io()),
+                _ => Err(error),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_: &File) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Returns the ranges of the messages (including the "From " line).
+fn split(data: &[u8]) -> Vec<Range<usize>> {
+    let mut starts = Vec::new();
+    let mut position = 0;
+    let mut previous_empty = true;
+
+    while position < data.len() {
+        let end = match data[position..].iter().position(|byte| *byte == b'\n') {
+            Some(offset) => position + offset + 1,
+            None => data.len(),
+        };
+        let line = &data[position..end];
+
+        if previous_empty && line.starts_with(b"From ") {
+            starts.push(position);
+        }
+
+        previous_empty = line == b"\n" || line == b"\r\n";
+        position = end;
+    }
+
+    let mut ranges = Vec::new();
+
+    for (index, start) in starts.iter().enumerate() {
+        let end = starts.get(index + 1).copied().unwrap_or(data.len());
+        ranges.push(*start..end);
+    }
+
+    ranges
+}
+
+/// Returns the message without the "From " line and the empty line separating it from the next
+/// message. Quoted "From " lines are unquoted once.
+fn content(raw: &[u8]) -> Vec<u8> {
+    let raw = match raw.iter().position(|byte| *byte == b'\n') {
+        Some(end) => &raw[end + 1..],
+        None => &[],
+    };
+
+    let length = if raw.ends_with(b"\r\n\r\n") {
+        raw.len() - 2
+    } else if raw.ends_with(b"\n\n") {
+        raw.len() - 1
+    } else {
+        raw.len()
+    };
+    let raw = &raw[..length];
+
+    let mut content = Vec::with_capacity(raw.len());
+
+    for line in raw.split_inclusive(|byte| *byte == b'\n') {
+        let quotes = line.iter().take_while(|byte| **byte == b'>').count();
+
+        if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            content.extend_from_slice(&line[1..]);
+        } else {
+            content.extend_from_slice(line);
+        }
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::AuthError;
+
+    const MBOX: &[u8] = b"From alice@example.org Thu Jan  1 00:00:00 1970\n\
+        Subject: One\n\
+        \n\
+        >From the start\n\
+        >>From quoted\n\
+        \n\
+        From bob@example.org Thu Jan  1 00:00:00 1970\n\
+        Subject: Two\n\
+        \n\
+        Hello\n\
+        From here\n\
+        \n";
+
+    /// Creates an mbox file in the temporary directory.
+    fn mbox(name: &str, data: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pop3-codec-mbox-{}-{}", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_mbox() {
+        let path = mbox("test", MBOX);
+
+        let mut maildrop = MboxMaildrop::open(&path).unwrap();
+        assert_eq!(maildrop.len(), 2);
+        assert_eq!(
+            maildrop.fetch(1).unwrap(),
+            b"Subject: One\n\nFrom the start\n>From quoted\n"
+        );
+        assert_eq!(maildrop.size(1), 46);
+        assert_eq!(
+            maildrop.fetch(2).unwrap(),
+            b"Subject: Two\n\nHello\nFrom here\n"
+        );
+        assert_eq!(
+            maildrop.uid(1),
+            format!(
+                "{:016x}",
+                stable_hash(b"Subject: One\n\nFrom the start\n>From quoted\n")
+            )
+        );
+
+        // The maildrop is locked.
+        let error = MboxMaildrop::open(&path).unwrap_err();
+        assert!(matches!(AuthError::from(error), AuthError::InUse));
+
+        // A message is delivered (without respecting the lock).
+        let delivered = b"From carol@example.org Thu Jan  1 00:00:00 1970\nSubject: Three\n\n";
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(delivered)
+            .unwrap();
+
+        maildrop.commit(&[1]).unwrap();
+        drop(maildrop);
+
+        let maildrop = MboxMaildrop::open(&path).unwrap();
+        assert_eq!(maildrop.len(), 2);
+        assert_eq!(
+            maildrop.uid(1),
+            format!(
+                "{:016x}",
+                stable_hash(b"Subject: Two\n\nHello\nFrom here\n")
+            )
+        );
+        assert_eq!(
+            maildrop.uid(2),
+            format!("{:016x}", stable_hash(b"Subject: Three\n"))
+        );
+        drop(maildrop);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dotlock() {
+        let path = mbox("dotlock", b"");
+        let lock = PathBuf::from(format!("{}.lock", path.display()));
+
+        let dotlock = Dotlock::acquire(&path).unwrap();
+        assert_eq!(fs::read(&lock).unwrap(), dotlock.token);
+        assert!(MaildropLocked::is(&Dotlock::acquire(&path).unwrap_err()));
+
+        refresh(&lock, &dotlock.token).unwrap();
+        assert!(refresh(&lock, b"other").is_err());
+
+        // Another process replaced the lock, e.g., because it was considered stale.
+        fs::write(&lock, b"other").unwrap();
+        assert!(refresh(&lock, &dotlock.token).is_err());
+        drop(dotlock);
+        assert_eq!(fs::read(&lock).unwrap(), b"other");
+
+        fs::remove_file(&lock).unwrap();
+        drop(Dotlock::acquire(&path).unwrap());
+        assert!(!lock.exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_uids() {
+        let path = mbox(
+            "duplicates",
+            b"From a\nSubject: Same\n\nOne\n\nFrom a\nSubject: Same\n\nTwo\n\n\
+              From a\nSubject: Same\n\nTwo\n",
+        );
+
+        let mut maildrop = MboxMaildrop::open(&path).unwrap();
+        assert_eq!(maildrop.len(), 3);
+        let hash = stable_hash(b"Subject: Same\n\nTwo\n");
+        assert_eq!(maildrop.uid(2), format!("{:016x}", hash));
+        // Identical messages are distinguished by a suffix.
+        assert_eq!(maildrop.uid(3), format!("{:016x}.1", hash));
+        maildrop.commit(&[1, 3]).unwrap();
+        drop(maildrop);
+
+        // The unique-id does not depend on the position.
+        let maildrop = MboxMaildrop::open(&path).unwrap();
+        assert_eq!(maildrop.len(), 1);
+        assert_eq!(maildrop.uid(1), format!("{:016x}", hash));
+        drop(maildrop);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_preamble() {
+        let path = mbox("preamble", b"garbage\n\nFrom a\nOne\n\nFrom b\nTwo\n");
+
+        let mut maildrop = MboxMaildrop::open(&path).unwrap();
+        assert_eq!(maildrop.len(), 2);
+        maildrop.commit(&[1]).unwrap();
+        drop(maildrop);
+
+        assert_eq!(fs::read(&path).unwrap(), b"garbage\n\nFrom b\nTwo\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(b""), vec![]);
+        assert_eq!(split(b"From a\nx\n\nFrom b\ny\n"), vec![0..10, 10..19]);
+        // "From " is only a separator at the start or after an empty line.
+        assert_eq!(split(b"From a\nx\nFrom b\n"), vec![0..16]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+
+use crate::server::{message_size, Maildrop};
+
+/// In-memory maildrop, e.g., for tests.
+///
+/// Clones share the same messages. Every message gets a deterministic unique-id on delivery
+/// ("uid00000001", "uid00000002", ...).
+///
+/// A session works on a [MemorySnapshot] taken by [open](MemoryMaildrop::open), i.e., the
+/// numbering of the messages does not change when messages are delivered during the session.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMaildrop {
+    store: Arc<Mutex<Store>>,
+}
+
+/// Messages of a [MemoryMaildrop] at the start of a session.
+///
+/// Deleted messages are removed from the shared messages on [commit](Maildrop::commit).
+#[derive(Clone, Debug)]
+pub struct MemorySnapshot {
+    store: Arc<Mutex<Store>>,
+    messages: Vec<Message>,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    /// Used to assign the unique-ids.
+    counter: u32,
+    messages: Vec<Message>,
+}
+
+#[derive(Clone, Debug)]
+struct Message {
+    uid: String,
+    data: Arc<[u8]>,
+}
+
+impl MemoryMaildrop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a maildrop containing `messages`.
+    pub fn with_messages<I, M>(messages: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<Vec<u8>>,
+    {
+        let maildrop = Self::new();
+
+        for message in messages {
+            maildrop.deliver(message);
+        }
+
+        maildrop
+    }
+
+    /// Adds a message and returns its unique-id.
+    pub fn deliver<M>(&self, message: M) -> String
+    where
+        M: Into<Vec<u8>>,
+    {
+        let mut store = self.store.lock().unwrap();
+
+        store.counter += 1;
+        let uid = format!("uid{:08}", store.counter);
+
+        store.messages.push(Message {
+            uid: uid.clone(),
+            data: message.into().into(),
+        });
+
+        uid
+    }
+
+    /// Returns all messages (which were not deleted yet).
+    pub fn messages(&self) -> Vec<Vec<u8>> {
+        let store = self.store.lock().unwrap();
+
+        store
+            .messages
+            .iter()
+            .map(|message| message.data.to_vec())
+            .collect()
+    }
+
+    /// Returns a maildrop for a new session, i.e., a snapshot of the current messages.
+    pub fn open(&self) -> MemorySnapshot {
+        let store = self.store.lock().unwrap();
+
+        MemorySnapshot {
+            store: self.store.clone(),
+            messages: store.messages.clone(),
+        }
+    }
+}
+
+impl MemorySnapshot {
+    fn message(&self, msg: u32) -> &Message {
+        &self.messages[msg as usize - 1]
+    }
+}
+
+impl Maildrop for MemorySnapshot {
+    fn len(&self) -> u32 {
+        self.messages.len() as u32
+    }
+
+    fn size(&self, msg: u32) -> u64 {
+        message_size(&self.message(msg).data)
+    }
+
+    fn uid(&self, msg: u32) -> String {
+        self.message(msg).uid.clone()
+    }
+
+    fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>> {
+        Ok(self.message(msg).data.to_vec())
+    }
+
+    fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()> {
+        let deleted: Vec<&str> = deleted
+            .iter()
+            .map(|msg| self.message(*msg).uid.as_str())
+            .collect();
+
+        let mut store = self.store.lock().unwrap();
+        store
+            .messages
+            .retain(|message| !deleted.contains(&message.uid.as_str()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_maildrop() {
+        let maildrop = MemoryMaildrop::with_messages(vec![&b"One\n"[..], b"Two\r\n"]);
+
+        let mut session = maildrop.open();
+        assert_eq!(session.len(), 2);
+        assert_eq!(session.size(1), 5);
+        assert_eq!(session.uid(2), "uid00000002");
+
+        // Not visible in the current session.
+        assert_eq!(maildrop.deliver(&b"Three\n"[..]), "uid00000003");
+        assert_eq!(session.len(), 2);
+
+        session.commit(&[1]).unwrap();
+        assert_eq!(
+            maildrop.messages(),
+            vec![b"Two\r\n".to_vec(), b"Three\n".to_vec()]
+        );
+
+        let session = maildrop.open();
+        assert_eq!(session.len(), 2);
+        assert_eq!(session.uid(1), "uid00000002");
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::server::{AuthError, Authenticator, Maildrop, MemoryMaildrop, MemorySnapshot, Server};
+
+/// Fault injected by the [MockServer].
+///
+/// Every fault is triggered once, i.e., it is removed when it was triggered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The next successful login is answered with "-ERR [IN-USE]".
+    InUse,
+    /// The next successful login is answered with "-ERR [SYS/TEMP]".
+    SysTemp,
+    /// The next RETR or TOP of message `msg` is answered with "-ERR [SYS/TEMP]".
+    FetchFailed { msg: u32 },
+    /// The connection is closed after `after` octets of the next response to RETR or TOP of
+    /// message `msg` were sent.
+    Disconnect { msg: u32, after: usize },
+}
+
+/// POP3 server for testing clients.
+///
+/// The server listens on a random port of the loopback interface and handles every connection
+/// in its own thread. It accepts a single user, whose messages are stored in a [MemoryMaildrop].
+/// Messages deleted by a client are removed from the maildrop.
+///
+/// ```rust
+/// use pop3_codec::{
+///     client::BlockingClient,
+///     server::{Fault, MemoryMaildrop, MockServer},
+///     types::Response,
+/// };
+///
+/// let maildrop = MemoryMaildrop::with_messages(vec!["Subject: Hello\r\n\r\nWorld\r\n"]);
+/// let server = MockServer::start("alice", "secret", maildrop).unwrap();
+/// server.inject(Fault::InUse);
+///
+/// let mut client = BlockingClient::connect_tcp(server.addr(), None).unwrap();
+/// client.user("alice").unwrap().unwrap();
+/// assert!(matches!(client.pass("secret"), Ok(Response::Err(_))));
+/// ```
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    maildrop: MemoryMaildrop,
+    faults: Arc<Mutex<VecDeque<Fault>>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts a server, which accepts `user` with `password`.
+    pub fn start(user: &str, password: &str, maildrop: MemoryMaildrop) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let faults = Arc::new(Mutex::new(VecDeque::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let credentials = (user.to_owned(), password.to_owned());
+        let thread = {
+            let maildrop = maildrop.clone();
+            let faults = faults.clone();
+            let stopped = stopped.clone();
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+
+                    let cut = Arc::new(Mutex::new(None));
+                    let server = Server::new(MockAuthenticator {
+                        credentials: credentials.clone(),
+                        maildrop: maildrop.clone(),
+                        faults: faults.clone(),
+                        cut: cut.clone(),
+                    });
+
+                    thread::spawn(move || server.handle(FaultyStream { stream, cut }));
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            maildrop,
+            faults,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the address to connect to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the maildrop, e.g., to deliver messages or to check which messages were deleted.
+    pub fn maildrop(&self) -> &MemoryMaildrop {
+        &self.maildrop
+    }
+
+    /// Adds a fault, which is triggered by one of the following connections.
+    ///
+    /// Faults are scripted in the order they were injected.
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().unwrap().push_back(fault);
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // Wake up the listener (connections, which are still open, are not affected).
+        if TcpStream::connect(self.addr).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Removes and returns the first fault matching `predicate`.
+fn trigger<F>(faults: &Mutex<VecDeque<Fault>>, predicate: F) -> Option<Fault>
+where
+    F: Fn(&Fault) -> bool,
+{
+    let mut faults = faults.lock().unwrap();
+    let index = faults.iter().position(predicate)?;
+
+    faults.remove(index)
+}
+
+struct MockAuthenticator {
+    credentials: (String, String),
+    maildrop: MemoryMaildrop,
+    faults: Arc<Mutex<VecDeque<Fault>>>,
+    /// Number of octets to send before the connection is closed, see [FaultyStream].
+    cut: Arc<Mutex<Option<usize>>>,
+}
+
+impl Authenticator for MockAuthenticator {
+    type Maildrop = MockMaildrop;
+
+    fn login(&self, user: &str, password: &str) -> Result<MockMaildrop, AuthError> {
+        if (user, password) != (&self.credentials.0, &self.credentials.1) {
+            return Err(AuthError::Invalid);
+        }
+
+        match trigger(&self.faults, |fault| {
+            matches!(fault, Fault::InUse | Fault::SysTemp)
+        }) {
+            Some(Fault::InUse) => Err(AuthError::InUse),
+            Some(_) => Err(AuthError::Io(ErrorKind::Other.into())),
+            None => Ok(MockMaildrop {
+                inner: self.maildrop.open(),
+                faults: self.faults.clone(),
+                cut: self.cut.clone(),
+            }),
+        }
+    }
+}
+
+struct MockMaildrop {
+    inner: MemorySnapshot,
+    faults: Arc<Mutex<VecDeque<Fault>>>,
+    cut: Arc<Mutex<Option<usize>>>,
+}
+
+impl Maildrop for MockMaildrop {
+    fn len(&self) -> u32 {
+        self.inner.len()
+    }
+
+    fn size(&self, msg: u32) -> u64 {
+        self.inner.size(msg)
+    }
+
+    fn uid(&self, msg: u32) -> String {
+        self.inner.uid(msg)
+    }
+
+    fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>> {
+        match trigger(&self.faults, |fault| match fault {
+            Fault::FetchFailed { msg: faulty } | Fault::Disconnect { msg: faulty, .. } => {
+                *faulty == msg
+            }
+            _ => false,
+        }) {
+            Some(Fault::Disconnect { after, .. }) => {
+                // The response is written right after the message was fetched.
+                *self.cut.lock().unwrap() = Some(after);
+                self.inner.fetch(msg)
+            }
+            Some(_) => Err(ErrorKind::Other.into()),
+            None => self.inner.fetch(msg),
+        }
+    }
+
+    fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()> {
+        self.inner.commit(deleted)
+    }
+}
+
+/// Stream, which is closed in the middle of a response on request.
+struct FaultyStream {
+    stream: TcpStream,
+    cut: Arc<Mutex<Option<usize>>>,
+}
+
+impl Read for FaultyStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for FaultyStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.cut.lock().unwrap().take() {
+            Some(after) => {
+                self.stream.write_all(&buf[..after.min(buf.len())])?;
+                self.stream.shutdown(Shutdown::Both)?;
+
+                Err(ErrorKind::ConnectionAborted.into())
+            }
+            None => self.stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        client::BlockingClient,
+        types::{MessageNumber, Response, ResponseCode},
+    };
+
+    const MESSAGES: [&str; 2] = [
+        "Subject: One\r\n\r\nLine 1\r\nLine 2\r\n",
+        "Subject: Two\r\n\r\nHello\r\n",
+    ];
+
+    fn login(server: &MockServer) -> BlockingClient<crate::client::TcpConnection> {
+        let mut client = BlockingClient::connect_tcp(server.addr(), None).unwrap();
+        client.user("alice").unwrap().unwrap();
+        client.pass("secret").unwrap().unwrap();
+
+        client
+    }
+
+    #[test]
+    fn test_mock_server() {
+        let server =
+            MockServer::start("alice", "secret", MemoryMaildrop::with_messages(MESSAGES)).unwrap();
+
+        let mut client = login(&server);
+        assert_eq!(client.stat().unwrap().unwrap().message_count, 2);
+        assert_eq!(
+            client
+                .uidl(MessageNumber::new(2).unwrap())
+                .unwrap()
+                .unwrap()
+                .message_uid,
+            "uid00000002".to_owned()
+        );
+        assert_eq!(
+            client
+                .retr(MessageNumber::new(2).unwrap())
+                .unwrap()
+                .unwrap()
+                .body_bytes(),
+            MESSAGES[1].as_bytes()
+        );
+        client
+            .dele(MessageNumber::new(1).unwrap())
+            .unwrap()
+            .unwrap();
+        client.quit().unwrap().unwrap();
+
+        assert_eq!(
+            server.maildrop().messages(),
+            vec![MESSAGES[1].as_bytes().to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_mock_server_faults() {
+        let server =
+            MockServer::start("alice", "secret", MemoryMaildrop::with_messages(MESSAGES)).unwrap();
+        server.inject(Fault::InUse);
+        server.inject(Fault::SysTemp);
+        server.inject(Fault::FetchFailed { msg: 1 });
+        server.inject(Fault::Disconnect { msg: 2, after: 20 });
+
+        let mut client = BlockingClient::connect_tcp(server.addr(), None).unwrap();
+        client.user("alice").unwrap().unwrap();
+        match client.pass("wrong").unwrap() {
+            Response::Err(error) => assert_eq!(error.code, Some(ResponseCode::Auth)),
+            Response::Ok(_) => panic!(),
+        }
+        for code in [ResponseCode::InUse, ResponseCode::SysTemp] {
+            client.user("alice").unwrap().unwrap();
+            match client.pass("secret").unwrap() {
+                Response::Err(error) => assert_eq!(error.code, Some(code)),
+                Response::Ok(_) => panic!(),
+            }
+        }
+        client.user("alice").unwrap().unwrap();
+        client.pass("secret").unwrap().unwrap();
+
+        match client.retr(MessageNumber::new(1).unwrap()).unwrap() {
+            Response::Err(error) => assert!(error.code.unwrap().is_temporary()),
+            Response::Ok(_) => panic!(),
+        }
+        client
+            .retr(MessageNumber::new(1).unwrap())
+            .unwrap()
+            .unwrap();
+
+        client
+            .dele(MessageNumber::new(1).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(client.retr(MessageNumber::new(2).unwrap()).is_err());
+
+        // Nothing was deleted, because the connection was lost.
+        let mut client = login(&server);
+        assert_eq!(client.stat().unwrap().unwrap().message_count, 2);
+        client
+            .retr(MessageNumber::new(2).unwrap())
+            .unwrap()
+            .unwrap();
+        client.quit().unwrap().unwrap();
+    }
+}
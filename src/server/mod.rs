@@ -0,0 +1,229 @@
+//! Embeddable POP3 server.
+//!
+//! The [Server] implements the protocol (RFC 1939) on top of the
+//! [ServerSession](crate::session::ServerSession). Storage and authentication are delegated to the
+//! [Maildrop] and [Authenticator] traits.
+//!
+//! Messages marked as deleted are tracked by the server, i.e., RSET does not touch the maildrop.
+//! Deletions are passed to [Maildrop::commit] when the client sends QUIT in the TRANSACTION state.
+//! When the connection is lost before, nothing is deleted.
+//!
+//! The [MockServer] (backed by a [MemoryMaildrop]) is meant for testing clients, e.g., with
+//! scripted [faults](Fault).
+
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
+
+mod handler;
+mod maildir;
+mod mbox;
+mod memory;
+mod mock;
+
+pub use handler::Server;
+pub use maildir::MaildirMaildrop;
+pub use mbox::MboxMaildrop;
+pub use memory::{MemoryMaildrop, MemorySnapshot};
+pub use mock::{Fault, MockServer};
+
+/// Storage of the messages of a single user.
+///
+/// Messages are numbered from 1 to [len](Maildrop::len) and the numbering must not change
+/// during the session.
+pub trait Maildrop {
+    /// Returns the number of messages.
+    fn len(&self) -> u32;
+
+    /// Returns `true` when there are no messages.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the size of message `msg` in octets (using CRLF line endings).
+    fn size(&self, msg: u32) -> u64;
+
+    /// Returns the unique-id of message `msg`.
+    ///
+    /// The unique-id must be stable across sessions and consist of 1 to 70 characters in the
+    /// range 0x21 to 0x7E (RFC 1939).
+    fn uid(&self, msg: u32) -> String;
+
+    /// Returns the content of message `msg`.
+    fn fetch(&mut self, msg: u32) -> std::io::Result<Vec<u8>>;
+
+    /// Removes the messages in `deleted` (sorted in ascending order) and releases the maildrop.
+    fn commit(&mut self, deleted: &[u32]) -> std::io::Result<()>;
+}
+
+/// Verifies credentials and opens the maildrop of a user.
+pub trait Authenticator {
+    type Maildrop: Maildrop;
+
+    /// Verifies the credentials sent with USER and PASS.
+    fn login(&self, user: &str, password: &str) -> Result<Self::Maildrop, AuthError>;
+
+    /// Verifies the digest sent with APOP.
+    ///
+    /// The `timestamp` was sent in the greeting (without angle brackets). The expected digest is
+    /// hexlify(md5("<{timestamp}>{secret}")).
+    fn apop(&self, user: &str, timestamp: &str, digest: &str) -> Result<Self::Maildrop, AuthError> {
+        let _ = (user, timestamp, digest);
+
+        Err(AuthError::Unsupported)
+    }
+}
+
+/// Error returned by an [Authenticator].
+#[derive(Debug)]
+pub enum AuthError {
+    /// The credentials are invalid.
+    Invalid,
+    /// The authentication method is not supported.
+    Unsupported,
+    /// The maildrop is locked by another session.
+    InUse,
+    /// The maildrop could not be opened.
+    Io(std::io::Error),
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Invalid => write!(f, "invalid credentials"),
+            AuthError::Unsupported => write!(f, "authentication method not supported"),
+            AuthError::InUse => write!(f, "maildrop already locked"),
+            AuthError::Io(error) => write!(f, "unable to open maildrop: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AuthError {
+    /// Converts `error` into [AuthError::InUse] when it wraps [MaildropLocked], see
+    /// [MboxMaildrop::open]. Every other error is converted into [AuthError::Io].
+    fn from(error: std::io::Error) -> Self {
+        if MaildropLocked::is(&error) {
+            AuthError::InUse
+        } else {
+            AuthError::Io(error)
+        }
+    }
+}
+
+/// Error returned (wrapped in a [std::io::Error]) when a maildrop is locked by another session.
+///
+/// Use [into_io](MaildropLocked::into_io) to return it from a custom maildrop, so that it is
+/// converted into [AuthError::InUse].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaildropLocked;
+
+impl MaildropLocked {
+    /// Wraps the error into a [std::io::Error] of kind
+    /// [WouldBlock](std::io::ErrorKind::WouldBlock).
+    pub fn into_io(self) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::WouldBlock, self)
+    }
+
+    /// Returns `true` when `error` wraps [MaildropLocked].
+    pub fn is(error: &std::io::Error) -> bool {
+        matches!(error.get_ref(), Some(inner) if inner.is::<MaildropLocked>())
+    }
+}
+
+impl Display for MaildropLocked {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "maildrop already locked")
+    }
+}
+
+impl std::error::Error for MaildropLocked {}
+
+/// Splits `message` into lines (without line endings).
+///
+/// Both CRLF and bare LF are accepted as line ending. The lines borrow from `message`.
+pub(crate) fn lines(message: &[u8]) -> Vec<Cow<'_, [u8]>> {
+    split(message).map(Cow::Borrowed).collect()
+}
+
+/// Returns the size of `message` in octets when transmitted with CRLF line endings.
+///
+/// This matches the (unstuffed) body of the response to RETR, see [lines].
+pub(crate) fn message_size(message: &[u8]) -> u64 {
+    split(message).map(|line| line.len() as u64 + 2).sum()
+}
+
+fn split(message: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let message = message.strip_suffix(b"\n").unwrap_or(message);
+
+    let lines = match message.is_empty() {
+        true => None,
+        false => Some(message.split(|byte| *byte == b'\n')),
+    };
+
+    lines
+        .into_iter()
+        .flatten()
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+/// Returns a hash of `data`, which is stable across platforms and releases (FNV-1a).
+pub(crate) fn stable_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(lines(b""), Vec::<Vec<u8>>::new());
+        assert_eq!(lines(b"a\r\nb\n"), vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(
+            lines(b"a\r\n\r\nb"),
+            vec![b"a".to_vec(), b"".to_vec(), b"b".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_message_size() {
+        let tests: &[&[u8]] = &[b"", b"a", b"a\n", b"a\r\nb\n\n", b"\r\n\r\n.\r\n"];
+
+        for test in tests {
+            let expected: usize = lines(test).iter().map(|line| line.len() + 2).sum();
+            assert_eq!(message_size(test) as usize, expected);
+        }
+
+        assert_eq!(message_size(b"a\nb\n"), 6);
+    }
+
+    #[test]
+    fn test_auth_error() {
+        assert!(matches!(
+            AuthError::from(MaildropLocked.into_io()),
+            AuthError::InUse
+        ));
+        assert!(matches!(
+            AuthError::from(std::io::Error::from(std::io::ErrorKind::WouldBlock)),
+            AuthError::Io(_)
+        ));
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
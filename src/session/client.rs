@@ -0,0 +1,589 @@
+use std::collections::VecDeque;
+
+use crate::{
+    error::{DecodeError, DecodeResult},
+    parse::ParseOptions,
+    session::SessionError,
+    types::{AnyResponse, AuthData, AuthResponse, Capability, Command, IntoOwned, Response, State},
+};
+
+/// Sans-IO state machine for the client side of a POP3 session.
+///
+/// Commands are passed to [send](ClientSession::send), which returns the bytes to write to the
+/// server. Bytes received from the server are passed to [receive](ClientSession::receive).
+/// Afterwards, [next_event](ClientSession::next_event) uses the parser matching the pending
+/// command (see [parse::response](crate::parse::response)) and returns the typed response.
+///
+/// Challenges during AUTH are returned as [AuthResponse::Continue] and must be answered using
+/// [send_auth_data](ClientSession::send_auth_data).
+///
+/// ```rust
+/// use pop3_codec::{
+///     session::ClientSession,
+///     types::{AnyResponse, Command, State},
+/// };
+///
+/// let mut session = ClientSession::new();
+///
+/// session.receive(b"+OK POP3 server ready\r\n");
+/// assert!(matches!(
+///     session.next_event(),
+///     Ok(Some(AnyResponse::Greeting(_)))
+/// ));
+/// assert_eq!(session.state(), Some(State::Authorization));
+///
+/// let bytes = session.send(Command::User("alice".into())).unwrap();
+/// assert_eq!(bytes, b"USER alice\r\n");
+///
+/// session.receive(b"+OK ");
+/// assert_eq!(session.next_event(), Ok(None));
+/// session.receive(b"alice is welcome\r\n");
+/// assert!(matches!(
+///     session.next_event(),
+///     Ok(Some(AnyResponse::User(_)))
+/// ));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClientSession {
+    /// `None` until the greeting was received.
+    state: Option<State>,
+    closed: bool,
+    /// Commands waiting for their responses (in order).
+    pending: VecDeque<Command<'static>>,
+    /// The server announced PIPELINING in its last CAPA response.
+    pipelining: bool,
+    /// The server sent a challenge, which was not answered yet.
+    challenged: bool,
+    buffer: Vec<u8>,
+    options: ParseOptions,
+}
+
+impl ClientSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the greeting and the responses with `options` instead of the default ones.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the current state or `None` when the greeting was not received yet.
+    pub fn state(&self) -> Option<State> {
+        self.state
+    }
+
+    /// Returns `true` when the session was closed by QUIT.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the command, which is waiting for the next response (if any).
+    pub fn pending(&self) -> Option<&Command<'static>> {
+        self.pending.front()
+    }
+
+    /// Returns `true` when commands may be pipelined (RFC 2449).
+    ///
+    /// This is set automatically when a CAPA response is received and reset after STLS.
+    pub fn pipelining(&self) -> bool {
+        self.pipelining
+    }
+
+    /// Overrides whether commands may be pipelined, e.g., when the capabilities are known in advance.
+    pub fn set_pipelining(&mut self, pipelining: bool) {
+        self.pipelining = pipelining;
+    }
+
+    /// Prepares `command` for sending and returns the bytes to write to the server.
+    ///
+    /// Unless pipelining is used, only a single command may wait for its response at a time.
+    pub fn send(&mut self, command: Command<'_>) -> Result<Vec<u8>, SessionError> {
+        self.send_batch(vec![command])
+    }
+
+    /// Prepares a batch of commands for sending and returns the bytes to write to the server.
+    ///
+    /// Sending more than one command at a time (or sending while commands are waiting for their
+    /// responses) requires pipelining. Commands, which may change the state (PASS, APOP, AUTH,
+    /// STLS, and QUIT), must be the last command in a pipeline. The responses are returned in order
+    /// by [next_response](ClientSession::next_response).
+    pub fn send_batch(&mut self, commands: Vec<Command<'_>>) -> Result<Vec<u8>, SessionError> {
+        if self.closed {
+            return Err(SessionError::Closed);
+        }
+
+        let state = self.state.ok_or(SessionError::GreetingPending)?;
+
+        if !self.pending.is_empty() || commands.len() > 1 {
+            if !self.pipelining {
+                return Err(SessionError::CommandPending);
+            }
+
+            if self.pending.iter().any(changes_state) {
+                return Err(SessionError::CommandPending);
+            }
+        }
+
+        let last = commands.len().saturating_sub(1);
+
+        for (index, command) in commands.iter().enumerate() {
+            if !command.is_valid_in(state) {
+                return Err(SessionError::InvalidState {
+                    command: command.clone().into_owned(),
+                    state,
+                });
+            }
+
+            if index != last && changes_state(command) {
+                return Err(SessionError::NotPipelinable {
+                    command: command.clone().into_owned(),
+                });
+            }
+        }
+
+        let mut bytes = Vec::new();
+
+        for command in commands {
+            bytes.extend_from_slice(&command.serialize());
+            self.pending.push_back(command.into_owned());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Answers a challenge (see [AuthResponse::Continue]) and returns the bytes to write to the
+    /// server.
+    pub fn send_auth_data(&mut self, data: AuthData<'_>) -> Result<Vec<u8>, SessionError> {
+        if !self.challenged {
+            return Err(SessionError::NoChallenge);
+        }
+
+        self.challenged = false;
+
+        Ok(data.serialize())
+    }
+
+    /// Passes bytes received from the server to the session.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next event or `None` when more data must be received.
+    pub fn next_event(&mut self) -> Result<Option<AnyResponse<'static>>, SessionError> {
+        Ok(self.next()?.map(|(_, response)| response))
+    }
+
+    /// Returns the next response together with the command it belongs to or `None` when more
+    /// data must be received.
+    ///
+    /// Note: The greeting must be received using [next_event](ClientSession::next_event) first.
+    pub fn next_response(
+        &mut self,
+    ) -> Result<Option<(Command<'static>, AnyResponse<'static>)>, SessionError> {
+        if self.state.is_none() {
+            return Err(SessionError::GreetingPending);
+        }
+
+        match self.next()? {
+            Some((Some(command), response)) => Ok(Some((command, response))),
+            _ => Ok(None),
+        }
+    }
+
+    fn next(
+        &mut self,
+    ) -> Result<Option<(Option<Command<'static>>, AnyResponse<'static>)>, SessionError> {
+        let decoded = match (self.state, self.pending.front()) {
+            (None, _) => decode(
+                self.options
+                    .greeting(&self.buffer)
+                    .map(|(rem, greeting)| (rem, AnyResponse::Greeting(greeting))),
+                self.buffer.len(),
+            )?,
+            (Some(_), None) => {
+                if self.buffer.is_empty() {
+                    None
+                } else {
+                    return Err(SessionError::UnexpectedData);
+                }
+            }
+            (Some(_), Some(_)) if self.challenged => None,
+            (Some(_), Some(command)) => decode(
+                self.options.response(command, &self.buffer),
+                self.buffer.len(),
+            )?,
+        };
+
+        let (consumed, response) = match decoded {
+            Some(decoded) => decoded,
+            None => return Ok(None),
+        };
+
+        self.buffer.drain(..consumed);
+        let command = self.transition(&response);
+
+        Ok(Some((command, response)))
+    }
+
+    /// Updates the state and returns the command the response belongs to (if any).
+    fn transition(&mut self, response: &AnyResponse<'_>) -> Option<Command<'static>> {
+        match response {
+            AnyResponse::Greeting(_) => {
+                self.state = Some(State::Authorization);
+                return None;
+            }
+            AnyResponse::Auth(AuthResponse::Continue(_)) => {
+                self.challenged = true;
+                return self.pending.front().cloned();
+            }
+            AnyResponse::Pass(Response::Ok(_))
+            | AnyResponse::Apop(Response::Ok(_))
+            | AnyResponse::Auth(AuthResponse::Done(Response::Ok(_))) => {
+                self.state = Some(State::Transaction);
+            }
+            AnyResponse::Quit(_) => {
+                if self.state == Some(State::Transaction) {
+                    self.state = Some(State::Update);
+                }
+                self.closed = true;
+            }
+            AnyResponse::Capa(Response::Ok(capabilities)) => {
+                self.pipelining = capabilities.body.contains(&Capability::Pipelining);
+            }
+            AnyResponse::Stls(Response::Ok(_)) => {
+                // The client must not trust capabilities obtained before TLS (RFC 2595).
+                self.pipelining = false;
+            }
+            _ => {}
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+/// Returns `true` when the response to `command` may change the state of the session.
+fn changes_state(command: &Command<'_>) -> bool {
+    matches!(
+        command,
+        Command::Pass(_)
+            | Command::Apop { .. }
+            | Command::Auth { .. }
+            | Command::Stls
+            | Command::Quit
+    )
+}
+
+type Decoded = Option<(usize, AnyResponse<'static>)>;
+
+/// Detaches the decoded response from the buffer, so that the buffer can be drained.
+fn decode(
+    result: DecodeResult<'_, AnyResponse<'_>>,
+    length: usize,
+) -> Result<Decoded, SessionError> {
+    match result {
+        Ok((rem, response)) => Ok(Some((length - rem.len(), response.into_owned()))),
+        Err(DecodeError::Incomplete { .. }) => Ok(None),
+        Err(error) => Err(SessionError::Malformed(error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{DropListing, Mechanism, MessageNumber};
+
+    #[test]
+    fn test_example_session() {
+        let mut session = ClientSession::new();
+
+        assert_eq!(session.state(), None);
+        assert_eq!(
+            session.send(Command::Stat),
+            Err(SessionError::GreetingPending)
+        );
+
+        session.receive(b"+OK POP3 server ready <1896.697170952@dbc.mtview.ca.us>\r\n");
+        match session.next_event() {
+            Ok(Some(AnyResponse::Greeting(greeting))) => {
+                assert_eq!(
+                    greeting.timestamp.as_deref(),
+                    Some("1896.697170952@dbc.mtview.ca.us")
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(session.state(), Some(State::Authorization));
+
+        assert!(matches!(
+            session.send(Command::Stat),
+            Err(SessionError::InvalidState { .. })
+        ));
+        assert_eq!(
+            session.send(Command::ListAll).unwrap_err().to_string(),
+            "LIST is not valid in Authorization state"
+        );
+
+        let apop = Command::Apop {
+            name: "mrose".into(),
+            digest: "c4c9334bac560ecc979e58001b3e22fb".into(),
+        };
+        assert_eq!(
+            session.send(apop).unwrap(),
+            b"APOP mrose c4c9334bac560ecc979e58001b3e22fb\r\n"
+        );
+        assert_eq!(
+            session.send(Command::Noop),
+            Err(SessionError::CommandPending)
+        );
+
+        session.receive(b"+OK mrose's maildrop has 2 messages (320 octets)\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Apop(Response::Ok(_))))
+        ));
+        assert_eq!(session.state(), Some(State::Transaction));
+
+        session.send(Command::Stat).unwrap();
+        session.receive(b"+OK 2 320\r\n");
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Stat(Response::Ok(DropListing {
+                message_count: 2,
+                maildrop_size: 320,
+                trailing: None,
+            }))))
+        );
+
+        session
+            .send(Command::Retr {
+                msg: MessageNumber::new(1).unwrap(),
+            })
+            .unwrap();
+        session.receive(b"+OK 120 octets\r\n<the POP3 server sends message 1>\r\n");
+        assert_eq!(session.next_event(), Ok(None));
+        session.receive(b".\r\n");
+        match session.next_event() {
+            Ok(Some(AnyResponse::Retr(Response::Ok(message)))) => {
+                assert_eq!(
+                    message.body,
+                    vec![&b"<the POP3 server sends message 1>"[..]]
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+
+        session
+            .send(Command::Dele {
+                msg: MessageNumber::new(1).unwrap(),
+            })
+            .unwrap();
+        session.receive(b"+OK message 1 deleted\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Dele(Response::Ok(_))))
+        ));
+
+        session.send(Command::Quit).unwrap();
+        session.receive(b"+OK dewey POP3 server signing off\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Quit(Response::Ok(_))))
+        ));
+        assert_eq!(session.state(), Some(State::Update));
+        assert!(session.is_closed());
+        assert_eq!(session.send(Command::Noop), Err(SessionError::Closed));
+    }
+
+    #[test]
+    fn test_auth_session() {
+        let mut session = ClientSession::new();
+
+        session.receive(b"+OK POP3 server ready\r\n");
+        session.next_event().unwrap();
+
+        assert_eq!(
+            session.send_auth_data(AuthData::Cancel),
+            Err(SessionError::NoChallenge)
+        );
+
+        session
+            .send(Command::Auth {
+                mechanism: Mechanism::Plain,
+                initial_response: None,
+            })
+            .unwrap();
+
+        session.receive(b"+ \r\n");
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Auth(AuthResponse::Continue("".into()))))
+        );
+        assert_eq!(session.next_event(), Ok(None));
+
+        assert_eq!(
+            session
+                .send_auth_data(AuthData::Response("dGVzdAB0ZXN0AHRlc3Q=".into()))
+                .unwrap(),
+            b"dGVzdAB0ZXN0AHRlc3Q=\r\n"
+        );
+
+        session.receive(b"+OK Maildrop locked and ready\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(AnyResponse::Auth(AuthResponse::Done(Response::Ok(_)))))
+        ));
+        assert_eq!(session.state(), Some(State::Transaction));
+        assert_eq!(session.pending(), None);
+    }
+
+    #[test]
+    fn test_unexpected_data() {
+        let mut session = ClientSession::new();
+
+        session.receive(b"+OK POP3 server ready\r\n+OK\r\n");
+        session.next_event().unwrap();
+        assert_eq!(session.next_event(), Err(SessionError::UnexpectedData));
+
+        let mut session = ClientSession::new();
+
+        session.receive(b"* garbage\r\n");
+        assert_eq!(
+            session.next_event(),
+            Err(SessionError::Malformed(DecodeError::Failed {
+                offset: 0,
+                expected: "keyword",
+            }))
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let mut session = ClientSession::new().with_options(ParseOptions::strict());
+
+        session.receive(b"+ok POP3 server ready\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Err(SessionError::Malformed(_))
+        ));
+
+        let mut session = ClientSession::new().with_options(ParseOptions::permissive());
+
+        session.receive(b"+OK POP3 server ready\n");
+        session.next_event().unwrap();
+        session.send(Command::User("alice".into())).unwrap();
+        session.receive(b"+okalice is welcome\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(AnyResponse::User(Response::Ok(_))))
+        ));
+    }
+
+    #[test]
+    fn test_pipelining() {
+        let mut session = ClientSession::new();
+
+        session.receive(b"+OK POP3 server ready\r\n");
+        session.next_event().unwrap();
+
+        assert_eq!(
+            session.send_batch(vec![Command::Capa, Command::Noop]),
+            Err(SessionError::CommandPending)
+        );
+
+        session.send(Command::Capa).unwrap();
+        assert_eq!(
+            session.send(Command::Noop),
+            Err(SessionError::CommandPending)
+        );
+        session.receive(b"+OK\r\nUSER\r\nPIPELINING\r\n.\r\n");
+        assert!(matches!(
+            session.next_response(),
+            Ok(Some((Command::Capa, AnyResponse::Capa(_))))
+        ));
+        assert!(session.pipelining());
+
+        assert_eq!(
+            session.send_batch(vec![
+                Command::Pass("secret".into()),
+                Command::User("mrose".into()),
+            ]),
+            Err(SessionError::NotPipelinable {
+                command: Command::Pass("secret".into())
+            })
+        );
+
+        assert_eq!(
+            session
+                .send_batch(vec![
+                    Command::User("mrose".into()),
+                    Command::Pass("secret".into()),
+                ])
+                .unwrap(),
+            b"USER mrose\r\nPASS secret\r\n"
+        );
+        assert_eq!(
+            session.send(Command::Noop),
+            Err(SessionError::CommandPending)
+        );
+
+        session.receive(b"+OK\r\n+OK maildrop ready\r\n");
+        assert!(matches!(
+            session.next_response(),
+            Ok(Some((Command::User(_), AnyResponse::User(Response::Ok(_)))))
+        ));
+        assert!(matches!(
+            session.next_response(),
+            Ok(Some((Command::Pass(_), AnyResponse::Pass(Response::Ok(_)))))
+        ));
+        assert_eq!(session.state(), Some(State::Transaction));
+
+        assert_eq!(
+            session
+                .send_batch(vec![
+                    Command::Retr {
+                        msg: MessageNumber::new(1).unwrap(),
+                    },
+                    Command::Retr {
+                        msg: MessageNumber::new(2).unwrap(),
+                    },
+                    Command::Dele {
+                        msg: MessageNumber::new(1).unwrap(),
+                    },
+                ])
+                .unwrap(),
+            b"RETR 1\r\nRETR 2\r\nDELE 1\r\n"
+        );
+        session.send(Command::Quit).unwrap();
+
+        session.receive(b"+OK\r\none\r\n.\r\n-ERR no such message\r\n+OK\r\n+OK bye\r\n");
+
+        let mut responses = vec![];
+        while let Some(response) = session.next_response().unwrap() {
+            responses.push(response);
+        }
+
+        assert_eq!(responses.len(), 4);
+        assert!(matches!(
+            responses[0],
+            (Command::Retr { msg }, AnyResponse::Retr(Response::Ok(_))) if msg.get() == 1
+        ));
+        assert!(matches!(
+            responses[1],
+            (
+                Command::Retr { msg },
+                AnyResponse::Retr(Response::Err(_))
+            ) if msg.get() == 2
+        ));
+        assert!(matches!(
+            responses[2],
+            (Command::Dele { msg }, AnyResponse::Dele(Response::Ok(_))) if msg.get() == 1
+        ));
+        assert!(matches!(
+            responses[3],
+            (Command::Quit, AnyResponse::Quit(Response::Ok(_)))
+        ));
+        assert!(session.is_closed());
+    }
+}
@@ -0,0 +1,69 @@
+//! Sans-IO protocol state machines.
+//!
+//! The types in this module do not perform any I/O. Instead, received bytes are passed in and
+//! bytes to send are returned. This makes them usable with any (blocking or async) transport.
+
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    error::DecodeError,
+    types::{Command, State},
+};
+
+mod client;
+mod server;
+
+pub use client::ClientSession;
+pub use server::{ServerEvent, ServerSession};
+
+/// Error returned by a session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The greeting was not received yet.
+    GreetingPending,
+    /// A command is still waiting for its response.
+    CommandPending,
+    /// The command is not valid in the current state.
+    InvalidState {
+        command: Command<'static>,
+        state: State,
+    },
+    /// The command may change the state and must be the last command in a pipeline.
+    NotPipelinable { command: Command<'static> },
+    /// Authentication data was sent, but the server did not send a challenge.
+    NoChallenge,
+    /// No (matching) command is waiting for a response.
+    NoCommand,
+    /// The session was closed.
+    Closed,
+    /// Data was received, but no response was expected.
+    UnexpectedData,
+    /// Received data could not be parsed.
+    Malformed(DecodeError),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::GreetingPending => write!(f, "greeting was not received yet"),
+            SessionError::CommandPending => write!(f, "command is waiting for its response"),
+            SessionError::InvalidState { command, state } => {
+                write!(f, "{} is not valid in {:?} state", command.keyword(), state)
+            }
+            SessionError::NotPipelinable { command } => {
+                write!(
+                    f,
+                    "{} must be the last command in a pipeline",
+                    command.keyword()
+                )
+            }
+            SessionError::NoChallenge => write!(f, "no authentication challenge was received"),
+            SessionError::NoCommand => write!(f, "no matching command is waiting for a response"),
+            SessionError::Closed => write!(f, "session is closed"),
+            SessionError::UnexpectedData => write!(f, "received data without a pending command"),
+            SessionError::Malformed(error) => write!(f, "received data is malformed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
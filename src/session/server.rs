@@ -0,0 +1,524 @@
+use crate::{
+    error::DecodeError,
+    parse::ParseOptions,
+    session::SessionError,
+    types::{
+        AuthData, AuthResponse, Command, Greeting, IntoOwned, Response, SerializePayload,
+        SingleLine, State,
+    },
+};
+
+/// Sans-IO state machine for the server side of a POP3 session.
+///
+/// Bytes received from the client are passed to [receive](ServerSession::receive). Afterwards,
+/// [next_event](ServerSession::next_event) parses the next command. Commands, which are not valid
+/// in the current state, are rejected by the session itself. Valid commands are handed to the
+/// application, which answers them using [respond](ServerSession::respond).
+///
+/// ```rust
+/// use pop3_codec::{
+///     session::{ServerEvent, ServerSession},
+///     types::{Command, Greeting, Response, SingleLine, State},
+/// };
+///
+/// let mut session = ServerSession::new();
+///
+/// let bytes = session.greeting(Greeting {
+///     code: None,
+///     comment: "POP3 server ready".into(),
+///     timestamp: None,
+/// });
+/// assert_eq!(bytes, b"+OK POP3 server ready\r\n");
+///
+/// session.receive(b"STAT\r\nUSER alice\r\n");
+///
+/// match session.next_event().unwrap() {
+///     Some(ServerEvent::Rejected { response, .. }) => {
+///         assert!(response.starts_with(b"-ERR"));
+///     }
+///     other => panic!("{:?}", other),
+/// }
+///
+/// assert_eq!(
+///     session.next_event().unwrap(),
+///     Some(ServerEvent::Command(Command::User("alice".into())))
+/// );
+///
+/// let bytes = session
+///     .respond(Response::<SingleLine, SingleLine>::Ok(SingleLine {
+///         code: None,
+///         comment: "alice is welcome".into(),
+///     }))
+///     .unwrap();
+/// assert_eq!(bytes, b"+OK alice is welcome\r\n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServerSession {
+    state: State,
+    closed: bool,
+    pending: Option<Command<'static>>,
+    /// The client sent USER and the server accepted it.
+    user_accepted: bool,
+    /// A challenge was sent, which was not answered yet.
+    challenged: bool,
+    /// The current line is too long and is dropped until its end is received.
+    skipping: bool,
+    buffer: Vec<u8>,
+    options: ParseOptions,
+}
+
+/// Event produced by the [ServerSession].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerEvent {
+    /// A command, which is valid in the current state.
+    ///
+    /// Answer it using [respond](ServerSession::respond) (or [challenge](ServerSession::challenge)
+    /// in case of AUTH).
+    Command(Command<'static>),
+    /// The answer of the client to a challenge.
+    ///
+    /// Answer it using [respond](ServerSession::respond) or [challenge](ServerSession::challenge).
+    AuthData(AuthData<'static>),
+    /// The session rejected a command (or a line, which is not a command).
+    ///
+    /// The response must be written to the client. Invalid authentication data is rejected with
+    /// the AUTH command, which failed thereby.
+    Rejected {
+        command: Option<Command<'static>>,
+        response: Vec<u8>,
+    },
+}
+
+impl Default for ServerSession {
+    fn default() -> Self {
+        Self {
+            state: State::Authorization,
+            closed: false,
+            pending: None,
+            user_accepted: false,
+            challenged: false,
+            skipping: false,
+            buffer: Vec::new(),
+            options: ParseOptions::default(),
+        }
+    }
+}
+
+impl ServerSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the commands with `options` instead of the default ones.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the current state.
+    ///
+    /// Note: The session enters the UPDATE state as soon as QUIT is received in the TRANSACTION
+    /// state, i.e., before the application responds to it.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns `true` when the session was closed by QUIT.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the command, which is waiting for a response (if any).
+    pub fn pending(&self) -> Option<&Command<'static>> {
+        self.pending.as_ref()
+    }
+
+    /// Returns the bytes of the greeting to write to the client.
+    pub fn greeting(&self, greeting: Greeting<'_>) -> Vec<u8> {
+        greeting.serialize()
+    }
+
+    /// Passes bytes received from the client to the session.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next event or `None` when more data must be received.
+    ///
+    /// No further commands are parsed while a command is waiting for its response.
+    pub fn next_event(&mut self) -> Result<Option<ServerEvent>, SessionError> {
+        if self.closed {
+            return Err(SessionError::Closed);
+        }
+
+        if self.challenged {
+            return match self.options.auth_data(&self.buffer) {
+                Ok((rem, data)) => {
+                    let consumed = self.buffer.len() - rem.len();
+                    let data = data.into_owned();
+                    self.buffer.drain(..consumed);
+                    self.challenged = false;
+
+                    Ok(Some(ServerEvent::AuthData(data)))
+                }
+                Err(DecodeError::Incomplete { .. }) => Ok(None),
+                // The exchange ends with the rejection (RFC 5034, section 4).
+                Err(_) => {
+                    let command = self.pending.clone();
+
+                    match self.reject_line(command, "invalid authentication data") {
+                        Some(event) => {
+                            self.challenged = false;
+                            self.pending = None;
+
+                            Ok(Some(event))
+                        }
+                        None => Ok(None),
+                    }
+                }
+            };
+        }
+
+        if self.pending.is_some() {
+            return Ok(None);
+        }
+
+        if self.skipping {
+            return Ok(self.skip_line());
+        }
+
+        let command = match self.options.command(&self.buffer) {
+            Ok((rem, command)) => {
+                let consumed = self.buffer.len() - rem.len();
+                let command = command.into_owned();
+                self.buffer.drain(..consumed);
+                command
+            }
+            Err(DecodeError::Incomplete { .. }) => return Ok(None),
+            Err(DecodeError::LineTooLong { .. }) => return Ok(self.skip_line()),
+            Err(DecodeError::Failed { .. }) => return Ok(self.reject_line(None, "unknown command")),
+        };
+
+        if !command.is_valid_in(self.state) {
+            return Ok(Some(reject(command, "command not valid in this state")));
+        }
+
+        if matches!(command, Command::Pass(_)) && !self.user_accepted {
+            return Ok(Some(reject(command, "USER first")));
+        }
+
+        if command == Command::Quit && self.state == State::Transaction {
+            self.state = State::Update;
+        }
+
+        self.pending = Some(command.clone());
+
+        Ok(Some(ServerEvent::Command(command)))
+    }
+
+    /// Answers the pending command and returns the bytes to write to the client.
+    ///
+    /// Positive responses to PASS, APOP, and AUTH transition the session into the TRANSACTION
+    /// state. A response to QUIT closes the session.
+    pub fn respond<O>(
+        &mut self,
+        response: Response<O, SingleLine<'_>>,
+    ) -> Result<Vec<u8>, SessionError>
+    where
+        O: std::fmt::Debug + Clone + PartialEq + Eq + SerializePayload,
+    {
+        let command = match self.pending.take() {
+            Some(command) => command,
+            None => return Err(SessionError::NoCommand),
+        };
+
+        self.challenged = false;
+
+        let success = matches!(response, Response::Ok(_));
+
+        match command {
+            Command::User(_) => self.user_accepted = success,
+            Command::Pass(_) | Command::Apop { .. } | Command::Auth { .. } => {
+                self.user_accepted = false;
+
+                if success {
+                    self.state = State::Transaction;
+                }
+            }
+            Command::Quit => self.closed = true,
+            _ => {}
+        }
+
+        Ok(response.serialize())
+    }
+
+    /// Sends a (base64 encoded) challenge in response to AUTH and returns the bytes to write to
+    /// the client.
+    pub fn challenge(&mut self, challenge: String) -> Result<Vec<u8>, SessionError> {
+        match self.pending {
+            Some(Command::Auth { .. }) => {
+                self.challenged = true;
+
+                Ok(AuthResponse::Continue(challenge.into()).serialize())
+            }
+            _ => Err(SessionError::NoCommand),
+        }
+    }
+
+    /// Drops the current (too long) line and rejects it as soon as its end was received.
+    ///
+    /// This bounds the buffer, because the line is not kept in memory.
+    fn skip_line(&mut self) -> Option<ServerEvent> {
+        self.skipping = true;
+
+        match self.reject_line(None, "line too long") {
+            Some(event) => {
+                self.skipping = false;
+                Some(event)
+            }
+            None => {
+                self.buffer.clear();
+                None
+            }
+        }
+    }
+
+    /// Skips the current line and rejects it.
+    fn reject_line(
+        &mut self,
+        command: Option<Command<'static>>,
+        comment: &str,
+    ) -> Option<ServerEvent> {
+        let end = self.buffer.iter().position(|byte| *byte == b'\n')?;
+
+        self.buffer.drain(..=end);
+
+        Some(ServerEvent::Rejected {
+            command,
+            response: error(comment),
+        })
+    }
+}
+
+fn reject(command: Command<'static>, comment: &str) -> ServerEvent {
+    ServerEvent::Rejected {
+        command: Some(command),
+        response: error(comment),
+    }
+}
+
+fn error(comment: &str) -> Vec<u8> {
+    Response::<SingleLine, SingleLine>::Err(SingleLine {
+        code: None,
+        comment: comment.into(),
+    })
+    .serialize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{DropListing, MessageNumber, MultiLine, ScanListing};
+
+    fn ok() -> Response<SingleLine<'static>, SingleLine<'static>> {
+        Response::Ok(SingleLine {
+            code: None,
+            comment: "".into(),
+        })
+    }
+
+    #[test]
+    fn test_example_session() {
+        let mut session = ServerSession::new();
+
+        session.receive(b"PASS secret\r\nUSER mrose\r\nPASS secret\r\nSTAT\r\nLIST\r\nQUIT\r\n");
+
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Rejected {
+                command: Some(Command::Pass(_)),
+                ..
+            }))
+        ));
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::User("mrose".into()))))
+        );
+        assert_eq!(session.next_event(), Ok(None));
+        assert_eq!(session.respond(ok()).unwrap(), b"+OK\r\n");
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Pass("secret".into()))))
+        );
+        session.respond(ok()).unwrap();
+        assert_eq!(session.state(), State::Transaction);
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Stat)))
+        );
+        assert_eq!(
+            session
+                .respond(Response::Ok(DropListing {
+                    message_count: 2,
+                    maildrop_size: 320,
+                    trailing: None,
+                }))
+                .unwrap(),
+            b"+OK 2 320\r\n"
+        );
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::ListAll)))
+        );
+        assert_eq!(
+            session
+                .respond(Response::Ok(MultiLine {
+                    head: SingleLine {
+                        code: None,
+                        comment: "2 messages (320 octets)".into(),
+                    },
+                    body: vec![
+                        ScanListing {
+                            message_id: MessageNumber::new(1).unwrap(),
+                            message_size: 120,
+                            trailing: None,
+                        },
+                        ScanListing {
+                            message_id: MessageNumber::new(2).unwrap(),
+                            message_size: 200,
+                            trailing: None,
+                        },
+                    ],
+                }))
+                .unwrap(),
+            b"+OK 2 messages (320 octets)\r\n1 120\r\n2 200\r\n.\r\n".to_vec()
+        );
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Quit)))
+        );
+        assert_eq!(session.state(), State::Update);
+        session.respond(ok()).unwrap();
+        assert!(session.is_closed());
+        assert_eq!(session.next_event(), Err(SessionError::Closed));
+    }
+
+    #[test]
+    fn test_rejections() {
+        let mut session = ServerSession::new();
+
+        session.receive(b"FOO bar\r\nRETR 1\r\nCAPA\r\n");
+
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Rejected {
+                command: None,
+                response: b"-ERR unknown command\r\n".to_vec(),
+            }))
+        );
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Rejected {
+                command: Some(Command::Retr {
+                    msg: MessageNumber::new(1).unwrap()
+                }),
+                response: b"-ERR command not valid in this state\r\n".to_vec(),
+            }))
+        );
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Capa)))
+        );
+    }
+
+    #[test]
+    fn test_line_too_long() {
+        let mut session = ServerSession::new();
+
+        session.receive(b"USER ");
+        session.receive(&[b'a'; 300]);
+        assert_eq!(session.next_event(), Ok(None));
+        assert!(session.buffer.is_empty());
+
+        session.receive(b"aaa\r\nCAPA\r\n");
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Rejected {
+                command: None,
+                response: b"-ERR line too long\r\n".to_vec(),
+            }))
+        );
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Capa)))
+        );
+    }
+
+    #[test]
+    fn test_auth() {
+        let mut session = ServerSession::new();
+
+        session.receive(b"AUTH PLAIN\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::Auth { .. })))
+        ));
+        assert_eq!(session.challenge("".into()).unwrap(), b"+ \r\n");
+
+        assert_eq!(session.next_event(), Ok(None));
+        session.receive(b"dGVzdAB0ZXN0AHRlc3Q=\r\n");
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::AuthData(AuthData::Response(
+                "dGVzdAB0ZXN0AHRlc3Q=".into()
+            ))))
+        );
+
+        session.respond(ok()).unwrap();
+        assert_eq!(session.state(), State::Transaction);
+        assert_eq!(session.challenge("".into()), Err(SessionError::NoCommand));
+    }
+
+    #[test]
+    fn test_auth_invalid_data() {
+        let mut session = ServerSession::new();
+
+        session.receive(b"AUTH PLAIN\r\n");
+        session.next_event().unwrap();
+        session.challenge("".into()).unwrap();
+
+        // The exchange ends and the next line is a command again.
+        session.receive(b"not base64!\r\nUSER alice\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Rejected {
+                command: Some(Command::Auth { .. }),
+                ..
+            }))
+        ));
+        assert_eq!(session.pending(), None);
+        assert_eq!(
+            session.next_event(),
+            Ok(Some(ServerEvent::Command(Command::User("alice".into()))))
+        );
+        session.respond(ok()).unwrap();
+
+        session.receive(b"AUTH PLAIN\r\n");
+        session.next_event().unwrap();
+        session.challenge("".into()).unwrap();
+
+        // Responses are not limited like commands, e.g., GSSAPI tokens.
+        session.receive(&[b'A'; 2048]);
+        assert_eq!(session.next_event(), Ok(None));
+        session.receive(b"\r\n");
+        assert!(matches!(
+            session.next_event(),
+            Ok(Some(ServerEvent::AuthData(AuthData::Response(response)))) if response.len() == 2048
+        ));
+    }
+}
@@ -1,16 +1,29 @@
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
 #[cfg(feature = "serdex")]
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    error::DecodeError,
+    parse::is_auth_char,
+    types::{language::Language, IntoOwned, MessageNumber, State},
+};
+
 // 9. POP3 Command Summary
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub enum Command {
+pub enum Command<'a> {
     // Minimal POP3 Commands:
     // -- AUTHORIZATION state --
     /// USER name
-    User(String),
+    User(Cow<'a, str>),
     /// PASS string
-    Pass(String),
+    Pass(Cow<'a, str>),
     // -- TRANSACTION state --
     /// STAT
     Stat,
@@ -18,15 +31,15 @@ pub enum Command {
     ListAll,
     /// LIST msg
     List {
-        msg: u32,
+        msg: MessageNumber,
     },
     /// RETR msg
     Retr {
-        msg: u32,
+        msg: MessageNumber,
     },
     /// DELE msg
     Dele {
-        msg: u32,
+        msg: MessageNumber,
     },
     /// NOOP
     Noop,
@@ -40,20 +53,20 @@ pub enum Command {
     // -- AUTHORIZATION state --
     /// APOP name digest
     Apop {
-        name: String,
-        digest: String,
+        name: Cow<'a, str>,
+        digest: Cow<'a, str>,
     },
     // -- TRANSACTION state --
     /// TOP msg n
     Top {
-        msg: u32,
+        msg: MessageNumber,
         n: u32,
     },
     /// UIDL
     UidlAll,
     /// UIDL msg
     Uidl {
-        msg: u32,
+        msg: MessageNumber,
     },
 
     // RFC2449 (POP3 Extension Mechanism):
@@ -70,8 +83,8 @@ pub enum Command {
     // rfc5034? yes, but mechanism is required due to formal syntax.
     AuthAll,
     Auth {
-        mechanism: String,
-        initial_response: Option<String>,
+        mechanism: Mechanism,
+        initial_response: Option<Cow<'a, str>>,
     },
 
     // RFC6856
@@ -82,7 +95,7 @@ pub enum Command {
     },
 }
 
-impl Command {
+impl<'a> Command<'a> {
     pub fn name(&self) -> &'static str {
         match self {
             Command::User(_) => "USER",
@@ -109,6 +122,58 @@ impl Command {
         }
     }
 
+    /// Returns the keyword sent to the server, e.g., "LIST" for [ListAll](Command::ListAll) and
+    /// [List](Command::List).
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Command::ListAll => "LIST",
+            Command::UidlAll => "UIDL",
+            Command::AuthAll => "AUTH",
+            Command::LangAll => "LANG",
+            other => other.name(),
+        }
+    }
+
+    /// Returns `true` when the command may be sent in `state`.
+    ///
+    /// Note: No command is valid in the UPDATE state, because the server enters it after QUIT.
+    pub fn is_valid_in(&self, state: State) -> bool {
+        match state {
+            State::Authorization => matches!(
+                self,
+                Command::User(_)
+                    | Command::Pass(_)
+                    | Command::Quit
+                    | Command::Apop { .. }
+                    | Command::Capa
+                    | Command::Stls
+                    | Command::AuthAll
+                    | Command::Auth { .. }
+                    | Command::Utf8
+                    | Command::LangAll
+                    | Command::Lang { .. }
+            ),
+            State::Transaction => matches!(
+                self,
+                Command::Stat
+                    | Command::ListAll
+                    | Command::List { .. }
+                    | Command::Retr { .. }
+                    | Command::Dele { .. }
+                    | Command::Noop
+                    | Command::Rset
+                    | Command::Quit
+                    | Command::Top { .. }
+                    | Command::UidlAll
+                    | Command::Uidl { .. }
+                    | Command::Capa
+                    | Command::LangAll
+                    | Command::Lang { .. }
+            ),
+            State::Update => false,
+        }
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         match self {
             Command::User(user) => format!("USER {}\r\n", user).into_bytes(),
@@ -139,32 +204,264 @@ impl Command {
             },
             Command::Utf8 => b"UTF8\r\n".to_vec(),
             Command::LangAll => b"LANG\r\n".to_vec(),
-            Command::Lang { lang_or_wild } => {
-                format!("LANG {}\r\n", lang_or_wild.to_string()).into_bytes()
-            }
+            Command::Lang { lang_or_wild } => format!("LANG {}\r\n", lang_or_wild).into_bytes(),
+        }
+    }
+}
+
+/// Line sent by the client in response to a server challenge during the AUTH exchange (RFC 5034).
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum AuthData<'a> {
+    /// base64 encoded response (may be empty)
+    Response(Cow<'a, str>),
+    /// "*", i.e., cancel the authentication exchange
+    Cancel,
+}
+
+impl<'a> AuthData<'a> {
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            AuthData::Response(response) => format!("{}\r\n", response).into_bytes(),
+            AuthData::Cancel => b"*\r\n".to_vec(),
+        }
+    }
+}
+
+impl<'a> IntoOwned for Command<'a> {
+    type Owned = Command<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        match self {
+            Command::User(name) => Command::User(Cow::Owned(name.into_owned())),
+            Command::Pass(password) => Command::Pass(Cow::Owned(password.into_owned())),
+            Command::Stat => Command::Stat,
+            Command::ListAll => Command::ListAll,
+            Command::List { msg } => Command::List { msg },
+            Command::Retr { msg } => Command::Retr { msg },
+            Command::Dele { msg } => Command::Dele { msg },
+            Command::Noop => Command::Noop,
+            Command::Rset => Command::Rset,
+            Command::Quit => Command::Quit,
+            Command::Apop { name, digest } => Command::Apop {
+                name: Cow::Owned(name.into_owned()),
+                digest: Cow::Owned(digest.into_owned()),
+            },
+            Command::Top { msg, n } => Command::Top { msg, n },
+            Command::UidlAll => Command::UidlAll,
+            Command::Uidl { msg } => Command::Uidl { msg },
+            Command::Capa => Command::Capa,
+            Command::Stls => Command::Stls,
+            Command::AuthAll => Command::AuthAll,
+            Command::Auth {
+                mechanism,
+                initial_response,
+            } => Command::Auth {
+                mechanism,
+                initial_response: initial_response.into_owned(),
+            },
+            Command::Utf8 => Command::Utf8,
+            Command::LangAll => Command::LangAll,
+            Command::Lang { lang_or_wild } => Command::Lang { lang_or_wild },
+        }
+    }
+}
+
+impl<'a> IntoOwned for AuthData<'a> {
+    type Owned = AuthData<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        match self {
+            AuthData::Response(response) => AuthData::Response(Cow::Owned(response.into_owned())),
+            AuthData::Cancel => AuthData::Cancel,
         }
     }
 }
 
+/// SASL mechanism (RFC 4422), as used with AUTH (RFC 5034) and in the SASL capability.
+///
+/// Known mechanisms are matched case-insensitively. Other mechanisms must match the `auth_type`
+/// grammar of RFC 5034, i.e., consist of letters, digits, "-", and "_", which is checked when
+/// parsed from a string (or deserialized), see [OtherMechanism].
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub enum Language {
-    Lang(String),
-    Wild,
+pub enum Mechanism {
+    /// PLAIN (RFC 4616)
+    Plain,
+    /// LOGIN (draft-murchison-sasl-login)
+    Login,
+    /// CRAM-MD5 (RFC 2195)
+    CramMd5,
+    /// SCRAM-SHA-1 (RFC 5802)
+    ScramSha1,
+    /// SCRAM-SHA-1-PLUS (RFC 5802)
+    ScramSha1Plus,
+    /// SCRAM-SHA-256 (RFC 7677)
+    ScramSha256,
+    /// SCRAM-SHA-256-PLUS (RFC 7677)
+    ScramSha256Plus,
+    /// XOAUTH2 (Google, Microsoft)
+    XOAuth2,
+    /// OAUTHBEARER (RFC 7628)
+    OAuthBearer,
+    /// EXTERNAL (RFC 4422)
+    External,
+    /// ANONYMOUS (RFC 4505)
+    Anonymous,
+    /// GSSAPI (RFC 4752)
+    Gssapi,
+    Other(OtherMechanism),
 }
 
-impl ToString for Language {
-    fn to_string(&self) -> String {
+impl Mechanism {
+    pub fn as_str(&self) -> &str {
         match self {
-            Language::Lang(lang) => lang.clone(),
-            Language::Wild => "*".to_owned(),
+            Mechanism::Plain => "PLAIN",
+            Mechanism::Login => "LOGIN",
+            Mechanism::CramMd5 => "CRAM-MD5",
+            Mechanism::ScramSha1 => "SCRAM-SHA-1",
+            Mechanism::ScramSha1Plus => "SCRAM-SHA-1-PLUS",
+            Mechanism::ScramSha256 => "SCRAM-SHA-256",
+            Mechanism::ScramSha256Plus => "SCRAM-SHA-256-PLUS",
+            Mechanism::XOAuth2 => "XOAUTH2",
+            Mechanism::OAuthBearer => "OAUTHBEARER",
+            Mechanism::External => "EXTERNAL",
+            Mechanism::Anonymous => "ANONYMOUS",
+            Mechanism::Gssapi => "GSSAPI",
+            Mechanism::Other(other) => other.as_str(),
         }
     }
+
+    /// Converts a name, which was already validated by the parser.
+    pub(crate) fn from_valid(name: &str) -> Self {
+        Mechanism::known(name).unwrap_or_else(|| Mechanism::Other(OtherMechanism(name.into())))
+    }
+
+    /// Returns the known mechanism called `name` (ignoring case).
+    fn known(name: &str) -> Option<Self> {
+        let known = [
+            Mechanism::Plain,
+            Mechanism::Login,
+            Mechanism::CramMd5,
+            Mechanism::ScramSha1,
+            Mechanism::ScramSha1Plus,
+            Mechanism::ScramSha256,
+            Mechanism::ScramSha256Plus,
+            Mechanism::XOAuth2,
+            Mechanism::OAuthBearer,
+            Mechanism::External,
+            Mechanism::Anonymous,
+            Mechanism::Gssapi,
+        ];
+
+        known
+            .iter()
+            .find(|mechanism| mechanism.as_str().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+impl FromStr for Mechanism {
+    type Err = DecodeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        validate_mechanism(name)?;
+
+        Ok(Mechanism::from_valid(name))
+    }
+}
+
+impl Display for Mechanism {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Name of a mechanism, which is not known to this crate, see [Mechanism::Other].
+///
+/// The name is never empty, consists of letters, digits, "-", and "_", and never names a known
+/// mechanism, e.g., "PLAIN" is always [Mechanism::Plain]. Use [str::parse] to get a [Mechanism].
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serdex", serde(try_from = "String", into = "String"))]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct OtherMechanism(String);
+
+impl OtherMechanism {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for OtherMechanism {
+    type Error = DecodeError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        validate_mechanism(&name)?;
+
+        match Mechanism::known(&name) {
+            Some(_) => Err(DecodeError::Failed {
+                offset: 0,
+                expected: "unknown mechanism",
+            }),
+            None => Ok(Self(name)),
+        }
+    }
+}
+
+impl From<OtherMechanism> for String {
+    fn from(mechanism: OtherMechanism) -> Self {
+        mechanism.0
+    }
+}
+
+impl Display for OtherMechanism {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Checks that `name` matches `auth_type`.
+fn validate_mechanism(name: &str) -> Result<(), DecodeError> {
+    if name.is_empty() {
+        return Err(DecodeError::Failed {
+            offset: 0,
+            expected: "mechanism",
+        });
+    }
+
+    match name.bytes().position(|byte| !is_auth_char(byte)) {
+        Some(offset) => Err(DecodeError::Failed {
+            offset,
+            expected: "mechanism",
+        }),
+        None => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Command;
+    use std::convert::TryFrom;
+
+    use super::{AuthData, Command, Mechanism, OtherMechanism};
+    use crate::types::MessageNumber;
+
+    #[test]
+    fn test_message_number() {
+        assert_eq!(MessageNumber::new(0), None);
+        assert_eq!("12".parse::<MessageNumber>().unwrap().get(), 12);
+        assert!("0".parse::<MessageNumber>().is_err());
+        assert!("+1".parse::<MessageNumber>().is_err());
+        assert!("4294967296".parse::<MessageNumber>().is_err());
+        assert_eq!(MessageNumber::new(7).unwrap().to_string(), "7");
+    }
+
+    #[test]
+    fn test_keyword() {
+        assert_eq!(Command::ListAll.name(), "LISTALL");
+        assert_eq!(Command::ListAll.keyword(), "LIST");
+        assert_eq!(Command::LangAll.keyword(), "LANG");
+        assert_eq!(Command::Stat.keyword(), "STAT");
+    }
 
     #[test]
     fn test_serialize() {
@@ -175,9 +472,27 @@ mod test {
         );
         assert_eq!(Command::Stat.serialize(), b"STAT\r\n");
         assert_eq!(Command::ListAll.serialize(), b"LIST\r\n");
-        assert_eq!(Command::List { msg: 1 }.serialize(), b"LIST 1\r\n");
-        assert_eq!(Command::Retr { msg: 1 }.serialize(), b"RETR 1\r\n");
-        assert_eq!(Command::Dele { msg: 1 }.serialize(), b"DELE 1\r\n");
+        assert_eq!(
+            Command::List {
+                msg: MessageNumber::new(1).unwrap()
+            }
+            .serialize(),
+            b"LIST 1\r\n"
+        );
+        assert_eq!(
+            Command::Retr {
+                msg: MessageNumber::new(1).unwrap()
+            }
+            .serialize(),
+            b"RETR 1\r\n"
+        );
+        assert_eq!(
+            Command::Dele {
+                msg: MessageNumber::new(1).unwrap()
+            }
+            .serialize(),
+            b"DELE 1\r\n"
+        );
         assert_eq!(Command::Noop.serialize(), b"NOOP\r\n");
         assert_eq!(Command::Rset.serialize(), b"RSET\r\n");
         assert_eq!(Command::Quit.serialize(), b"QUIT\r\n");
@@ -189,14 +504,27 @@ mod test {
             .serialize(),
             b"APOP alice aabbccddeeff\r\n"
         );
-        assert_eq!(Command::Top { msg: 1, n: 5 }.serialize(), b"TOP 1 5\r\n");
+        assert_eq!(
+            Command::Top {
+                msg: MessageNumber::new(1).unwrap(),
+                n: 5
+            }
+            .serialize(),
+            b"TOP 1 5\r\n"
+        );
         assert_eq!(Command::UidlAll.serialize(), b"UIDL\r\n");
-        assert_eq!(Command::Uidl { msg: 1 }.serialize(), b"UIDL 1\r\n");
+        assert_eq!(
+            Command::Uidl {
+                msg: MessageNumber::new(1).unwrap()
+            }
+            .serialize(),
+            b"UIDL 1\r\n"
+        );
         assert_eq!(Command::Capa.serialize(), b"CAPA\r\n");
         assert_eq!(Command::Stls.serialize(), b"STLS\r\n");
         assert_eq!(
             Command::Auth {
-                mechanism: "PLAIN".into(),
+                mechanism: Mechanism::Plain,
                 initial_response: None
             }
             .serialize(),
@@ -204,7 +532,7 @@ mod test {
         );
         assert_eq!(
             Command::Auth {
-                mechanism: "PLAIN".into(),
+                mechanism: Mechanism::Plain,
                 initial_response: Some("XXX".into())
             }
             .serialize(),
@@ -212,4 +540,46 @@ mod test {
         );
         assert_eq!(Command::AuthAll.serialize(), b"AUTH\r\n");
     }
+
+    #[test]
+    fn test_serialize_auth_data() {
+        assert_eq!(
+            AuthData::Response("dGVzdAB0ZXN0AHRlc3Q=".into()).serialize(),
+            b"dGVzdAB0ZXN0AHRlc3Q=\r\n"
+        );
+        assert_eq!(AuthData::Response("".into()).serialize(), b"\r\n");
+        assert_eq!(AuthData::Cancel.serialize(), b"*\r\n");
+    }
+
+    #[test]
+    fn test_mechanism() {
+        assert_eq!("plain".parse::<Mechanism>().unwrap(), Mechanism::Plain);
+        assert_eq!(
+            "SCRAM-SHA-256-PLUS".parse::<Mechanism>().unwrap(),
+            Mechanism::ScramSha256Plus
+        );
+        assert_eq!(
+            "X_CUSTOM-1".parse::<Mechanism>().unwrap(),
+            Mechanism::Other(OtherMechanism::try_from(String::from("X_CUSTOM-1")).unwrap())
+        );
+        assert_eq!(
+            "X_CUSTOM-1".parse::<Mechanism>().unwrap().as_str(),
+            "X_CUSTOM-1"
+        );
+
+        // Known mechanisms and invalid names (e.g., when deserialized) are rejected.
+        assert!(OtherMechanism::try_from(String::from("plain")).is_err());
+        assert!(OtherMechanism::try_from(String::from("X\r\nDELE 1")).is_err());
+        assert!(OtherMechanism::try_from(String::from("")).is_err());
+        assert_eq!(Mechanism::XOAuth2.to_string(), "XOAUTH2");
+
+        assert!("".parse::<Mechanism>().is_err());
+        assert_eq!(
+            "PLAIN XXX".parse::<Mechanism>(),
+            Err(crate::error::DecodeError::Failed {
+                offset: 5,
+                expected: "mechanism"
+            })
+        );
+    }
 }
@@ -0,0 +1,472 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+#[cfg(feature = "serdex")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::DecodeError;
+
+/// Irregular grandfathered tags, which do not match the `langtag` production of RFC 5646.
+///
+/// Regular grandfathered tags (e.g., "zh-min-nan") are well-formed and parsed as usual.
+const IRREGULAR: &[&str] = &[
+    "en-GB-oed",
+    "i-ami",
+    "i-bnn",
+    "i-default",
+    "i-enochian",
+    "i-hak",
+    "i-klingon",
+    "i-lux",
+    "i-mingo",
+    "i-navajo",
+    "i-pwn",
+    "i-tao",
+    "i-tay",
+    "i-tsu",
+    "sgn-BE-FR",
+    "sgn-BE-NL",
+    "sgn-CH-DE",
+];
+
+/// Well-formed language tag (RFC 5646), e.g., "en", "de-CH", or "zh-Hant-TW".
+///
+/// Tags are stored in canonical case, i.e., comparison is case-insensitive and
+/// [Display] always outputs e.g., "sr-Latn-RS" (RFC 5646, section 2.1.1).
+///
+/// Note: Only the syntax is checked, i.e., subtags are not validated against the IANA registry.
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LanguageTag {
+    // Boxed to keep e.g., `Command` small.
+    subtags: Box<Subtags>,
+}
+
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Subtags {
+    /// Primary language subtag (empty for private-use and irregular grandfathered tags).
+    language: String,
+    extended_languages: Vec<String>,
+    script: Option<String>,
+    region: Option<String>,
+    variants: Vec<String>,
+    extensions: Vec<LanguageExtension>,
+    private_use: Vec<String>,
+    /// Irregular grandfathered tag, e.g., "i-default".
+    grandfathered: Option<String>,
+}
+
+/// Extension of a [LanguageTag], e.g., "u-co-phonebk".
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LanguageExtension {
+    /// Single letter or digit (except "x"), e.g., 'u'.
+    pub singleton: char,
+    pub subtags: Vec<String>,
+}
+
+impl LanguageTag {
+    /// Returns the primary language, e.g., "zh" for "zh-Hant-TW".
+    ///
+    /// Returns `None` for private-use tags (e.g., "x-whatever") and irregular grandfathered tags
+    /// (e.g., "i-default").
+    pub fn language(&self) -> Option<&str> {
+        match self.subtags.language.is_empty() {
+            true => None,
+            false => Some(&self.subtags.language),
+        }
+    }
+
+    /// Returns the extended language subtags, e.g., `["yue"]` for "zh-yue".
+    pub fn extended_languages(&self) -> &[String] {
+        &self.subtags.extended_languages
+    }
+
+    /// Returns the script, e.g., "Hant" for "zh-Hant-TW".
+    pub fn script(&self) -> Option<&str> {
+        self.subtags.script.as_deref()
+    }
+
+    /// Returns the region, e.g., "TW" for "zh-Hant-TW" or "419" for "es-419".
+    pub fn region(&self) -> Option<&str> {
+        self.subtags.region.as_deref()
+    }
+
+    /// Returns the variants, e.g., `["boont"]` for "en-boont".
+    pub fn variants(&self) -> &[String] {
+        &self.subtags.variants
+    }
+
+    pub fn extensions(&self) -> &[LanguageExtension] {
+        &self.subtags.extensions
+    }
+
+    /// Returns the private-use subtags, e.g., `["foo"]` for "en-x-foo".
+    pub fn private_use(&self) -> &[String] {
+        &self.subtags.private_use
+    }
+
+    /// Returns `true` for irregular grandfathered tags, e.g., "i-default".
+    pub fn is_grandfathered(&self) -> bool {
+        self.subtags.grandfathered.is_some()
+    }
+
+    /// Returns `true` when `range` matches this tag using "basic filtering" (RFC 4647).
+    ///
+    /// The range matches, when it is "*", equal to the tag, or a prefix of the tag followed by
+    /// "-", e.g., "de-CH" matches "de-CH-1996" but not "de-CHX". Matching is case-insensitive.
+    pub fn matches(&self, range: &Language) -> bool {
+        match range {
+            Language::Wild => true,
+            Language::Lang(range) => {
+                let tag = self.to_string();
+                let range = range.to_string();
+
+                tag.len() >= range.len()
+                    && tag[..range.len()].eq_ignore_ascii_case(&range)
+                    && matches!(tag.as_bytes().get(range.len()), None | Some(b'-'))
+            }
+        }
+    }
+}
+
+impl FromStr for LanguageTag {
+    type Err = DecodeError;
+
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        match parse(tag) {
+            Ok(subtags) => Ok(LanguageTag {
+                subtags: Box::new(subtags),
+            }),
+            Err(offset) => Err(DecodeError::Failed {
+                offset,
+                expected: "language tag",
+            }),
+        }
+    }
+}
+
+/// Parses a language tag or returns the offset of the first invalid subtag.
+fn parse(input: &str) -> Result<Subtags, usize> {
+    let mut tag = Subtags {
+        language: String::new(),
+        extended_languages: Vec::new(),
+        script: None,
+        region: None,
+        variants: Vec::new(),
+        extensions: Vec::new(),
+        private_use: Vec::new(),
+        grandfathered: None,
+    };
+
+    if let Some(grandfathered) = IRREGULAR
+        .iter()
+        .find(|irregular| irregular.eq_ignore_ascii_case(input))
+    {
+        tag.grandfathered = Some(grandfathered.to_string());
+        return Ok(tag);
+    }
+
+    // Subtags with their offset in `input`.
+    let mut subtags = Vec::new();
+    let mut offset = 0;
+
+    for subtag in input.split('-') {
+        if subtag.is_empty() || subtag.len() > 8 || !subtag.bytes().all(is_alphanum) {
+            return Err(offset);
+        }
+
+        subtags.push((offset, subtag.to_ascii_lowercase()));
+        offset += subtag.len() + 1;
+    }
+
+    let mut subtags = subtags.into_iter().peekable();
+
+    // language = 2*3ALPHA ["-" extlang] / 4ALPHA / 5*8ALPHA
+    // (The first subtag exists, because `split` yields at least one item.)
+    let (offset, first) = subtags.next().unwrap();
+
+    if first != "x" {
+        if first.len() < 2 || !first.bytes().all(is_alpha) {
+            return Err(offset);
+        }
+        tag.language = first;
+
+        // extlang = 3ALPHA *2("-" 3ALPHA)
+        if tag.language.len() <= 3 {
+            while tag.extended_languages.len() < 3 {
+                match subtags
+                    .next_if(|(_, subtag)| subtag.len() == 3 && subtag.bytes().all(is_alpha))
+                {
+                    Some((_, extlang)) => tag.extended_languages.push(extlang),
+                    None => break,
+                }
+            }
+        }
+
+        // script = 4ALPHA
+        if let Some((_, script)) =
+            subtags.next_if(|(_, subtag)| subtag.len() == 4 && subtag.bytes().all(is_alpha))
+        {
+            tag.script = Some(titlecase(&script));
+        }
+
+        // region = 2ALPHA / 3DIGIT
+        if let Some((_, region)) = subtags.next_if(|(_, subtag)| {
+            (subtag.len() == 2 && subtag.bytes().all(is_alpha))
+                || (subtag.len() == 3 && subtag.bytes().all(|byte| byte.is_ascii_digit()))
+        }) {
+            tag.region = Some(region.to_ascii_uppercase());
+        }
+
+        // variant = 5*8alphanum / (DIGIT 3alphanum)
+        while let Some((_, variant)) = subtags.next_if(|(_, subtag)| {
+            subtag.len() >= 5 || (subtag.len() == 4 && subtag.as_bytes()[0].is_ascii_digit())
+        }) {
+            tag.variants.push(variant);
+        }
+
+        // extension = singleton 1*("-" (2*8alphanum))
+        while let Some((offset, singleton)) =
+            subtags.next_if(|(_, subtag)| subtag.len() == 1 && subtag != "x")
+        {
+            let mut extension = LanguageExtension {
+                singleton: singleton.chars().next().unwrap(),
+                subtags: Vec::new(),
+            };
+
+            while let Some((_, subtag)) = subtags.next_if(|(_, subtag)| subtag.len() >= 2) {
+                extension.subtags.push(subtag);
+            }
+
+            if extension.subtags.is_empty() {
+                return Err(offset);
+            }
+
+            tag.extensions.push(extension);
+        }
+
+        match subtags.next() {
+            Some((_, x)) if x == "x" => {}
+            Some((offset, _)) => return Err(offset),
+            None => return Ok(tag),
+        }
+    }
+
+    // privateuse = "x" 1*("-" (1*8alphanum))
+    tag.private_use = subtags.map(|(_, subtag)| subtag).collect();
+
+    match tag.private_use.is_empty() {
+        true => Err(input.len()),
+        false => Ok(tag),
+    }
+}
+
+fn is_alpha(byte: u8) -> bool {
+    byte.is_ascii_alphabetic()
+}
+
+fn is_alphanum(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+}
+
+fn titlecase(subtag: &str) -> String {
+    let mut subtag = subtag.to_ascii_lowercase();
+    subtag[..1].make_ascii_uppercase();
+    subtag
+}
+
+impl Display for LanguageTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(grandfathered) = self.subtags.grandfathered.as_ref() {
+            return write!(f, "{}", grandfathered);
+        }
+
+        let mut subtags: Vec<&str> = Vec::new();
+
+        if !self.subtags.language.is_empty() {
+            subtags.push(&self.subtags.language);
+        }
+        subtags.extend(self.subtags.extended_languages.iter().map(String::as_str));
+        subtags.extend(self.subtags.script.as_deref());
+        subtags.extend(self.subtags.region.as_deref());
+        subtags.extend(self.subtags.variants.iter().map(String::as_str));
+
+        let mut out = subtags.join("-");
+
+        for extension in self.subtags.extensions.iter() {
+            out.push('-');
+            out.push(extension.singleton);
+
+            for subtag in extension.subtags.iter() {
+                out.push('-');
+                out.push_str(subtag);
+            }
+        }
+
+        if !self.subtags.private_use.is_empty() {
+            if !out.is_empty() {
+                out.push('-');
+            }
+            out.push('x');
+
+            for subtag in self.subtags.private_use.iter() {
+                out.push('-');
+                out.push_str(subtag);
+            }
+        }
+
+        write!(f, "{}", out)
+    }
+}
+
+/// Argument of LANG (RFC 6856), i.e., a basic language range (RFC 4647).
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Language {
+    Lang(LanguageTag),
+    Wild,
+}
+
+impl Language {
+    /// Selects the best matching tag from `available` using "lookup" (RFC 4647).
+    ///
+    /// The range is progressively truncated from the end until a tag equals the range, e.g.,
+    /// "de-CH-1996" is tried as "de-CH-1996", "de-CH", and "de". Single-letter subtags are removed
+    /// together with the preceding subtag. For "*", `None` is returned, i.e., the default should
+    /// be used.
+    pub fn lookup<'a>(&self, available: &'a [LanguageTag]) -> Option<&'a LanguageTag> {
+        let range = match self {
+            Language::Lang(range) => range.to_string(),
+            Language::Wild => return None,
+        };
+        let mut range = range.as_str();
+
+        loop {
+            if let Some(tag) = available
+                .iter()
+                .find(|tag| tag.to_string().eq_ignore_ascii_case(range))
+            {
+                return Some(tag);
+            }
+
+            let mut end = range.rfind('-')?;
+
+            // Remove a trailing singleton, e.g., "en-x" of "en-x-foo".
+            if end >= 2 && range.as_bytes()[end - 2] == b'-' {
+                end -= 2;
+            }
+
+            range = &range[..end];
+        }
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Language::Lang(lang) => write!(f, "{}", lang),
+            Language::Wild => write!(f, "*"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tag(tag: &str) -> LanguageTag {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let zh = tag("ZH-hant-tw");
+        assert_eq!(zh.language(), Some("zh"));
+        assert_eq!(zh.script(), Some("Hant"));
+        assert_eq!(zh.region(), Some("TW"));
+        assert_eq!(zh.to_string(), "zh-Hant-TW");
+
+        let tests = [
+            "de",
+            "mul",
+            "zh-yue-HK",
+            "es-419",
+            "en-boont",
+            "de-CH-1996",
+            "sl-rozaj-biske",
+            "en-US-u-islamcal",
+            "en-a-bbb-x-a-ccc",
+            "x-whatever",
+            "qaa-Qaaa-QM-x-southern",
+            "i-default",
+            "en-GB-oed",
+        ];
+
+        for test in tests {
+            assert_eq!(tag(test).to_string(), test);
+        }
+
+        let extended = tag("en-US-u-co-phonebk-x-private");
+        assert_eq!(
+            extended.extensions(),
+            &[LanguageExtension {
+                singleton: 'u',
+                subtags: vec!["co".into(), "phonebk".into()],
+            }]
+        );
+        assert_eq!(extended.private_use(), &["private".to_owned()]);
+        assert!(tag("I-DEFAULT").is_grandfathered());
+
+        // Not well-formed.
+        let tests = [
+            ("", 0),
+            ("a", 0),
+            ("de-", 3),
+            ("de--CH", 3),
+            ("en-a-bbb-a", 9),
+            ("ar-a-aaa-b-bbb-a-ccc-x", 22),
+            ("123", 0),
+            ("de-DE-toolongsubtag", 6),
+            ("en-US-US", 6),
+            ("en_US", 0),
+        ];
+
+        for (test, offset) in tests {
+            assert_eq!(
+                test.parse::<LanguageTag>(),
+                Err(DecodeError::Failed {
+                    offset,
+                    expected: "language tag"
+                }),
+                "{}",
+                test
+            );
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let range = |range: &str| Language::Lang(tag(range));
+
+        assert!(tag("de-CH-1996").matches(&range("de-ch")));
+        assert!(tag("de-CH").matches(&range("de")));
+        assert!(!tag("de").matches(&range("de-CH")));
+        assert!(!tag("den").matches(&range("de")));
+        assert!(tag("de").matches(&Language::Wild));
+
+        let available = [tag("en"), tag("de"), tag("de-CH"), tag("i-default")];
+        assert_eq!(range("de-CH-1996").lookup(&available), Some(&available[2]));
+        assert_eq!(range("de-AT").lookup(&available), Some(&available[1]));
+        assert_eq!(
+            range("en-a-bbb-x-foo").lookup(&available),
+            Some(&available[0])
+        );
+        assert_eq!(range("I-Default").lookup(&available), Some(&available[3]));
+        assert_eq!(range("fr").lookup(&available), None);
+        assert_eq!(Language::Wild.lookup(&available), None);
+    }
+}
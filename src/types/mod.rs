@@ -1,12 +1,142 @@
+//! Commands and responses.
+//!
+//! Types borrow from the parsed input where possible, e.g., `SingleLine<'a>` holds a
+//! `Cow<'a, str>`. Use [IntoOwned::into_owned] to detach them from the input buffer.
+
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    num::NonZeroU32,
+    str::FromStr,
+};
+
+use crate::error::DecodeError;
+
 pub(crate) mod command;
+pub(crate) mod language;
 pub(crate) mod response;
 
-pub use command::{Command, Language};
-pub use response::Response;
+pub use command::{AuthData, Command, Mechanism, OtherMechanism};
+pub use language::{Language, LanguageExtension, LanguageTag};
+pub use response::{
+    AnyResponse, AuthResponse, Capability, DropListing, ExpirePolicy, Greeting, LanguageListing,
+    MultiLine, Response, ResponseCode, ScanListing, SerializeLine, SerializePayload, SingleLine,
+    UniqueIdListing,
+};
 
-#[derive(Clone)]
+/// Session state as defined in RFC 1939.
+#[cfg_attr(feature = "serdex", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum State {
     Authorization,
     Transaction,
     Update,
 }
+
+/// Number of a message in the maildrop, i.e., a positive integer (RFC 1939).
+///
+/// Note: Message numbers are only valid within a single session.
+#[cfg_attr(feature = "serdex", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageNumber(NonZeroU32);
+
+impl MessageNumber {
+    /// Returns `None` when `number` is 0.
+    pub fn new(number: u32) -> Option<Self> {
+        NonZeroU32::new(number).map(Self)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl From<NonZeroU32> for MessageNumber {
+    fn from(number: NonZeroU32) -> Self {
+        Self(number)
+    }
+}
+
+impl From<MessageNumber> for u32 {
+    fn from(number: MessageNumber) -> Self {
+        number.get()
+    }
+}
+
+impl TryFrom<u32> for MessageNumber {
+    type Error = DecodeError;
+
+    fn try_from(number: u32) -> Result<Self, Self::Error> {
+        Self::new(number).ok_or(DecodeError::Failed {
+            offset: 0,
+            expected: "message number",
+        })
+    }
+}
+
+impl FromStr for MessageNumber {
+    type Err = DecodeError;
+
+    fn from_str(number: &str) -> Result<Self, Self::Err> {
+        match number.bytes().position(|byte| !byte.is_ascii_digit()) {
+            Some(offset) => Err(DecodeError::Failed {
+                offset,
+                expected: "message number",
+            }),
+            None => number
+                .parse::<u32>()
+                .map_err(|_| DecodeError::Failed {
+                    offset: 0,
+                    expected: "message number",
+                })
+                .and_then(Self::try_from),
+        }
+    }
+}
+
+impl Display for MessageNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Conversion into a type, which owns all its data, i.e., does not borrow from the input anymore.
+pub trait IntoOwned {
+    type Owned: 'static;
+
+    fn into_owned(self) -> Self::Owned;
+}
+
+impl<'a, B> IntoOwned for Cow<'a, B>
+where
+    B: ToOwned + ?Sized + 'static,
+{
+    type Owned = Cow<'static, B>;
+
+    fn into_owned(self) -> Cow<'static, B> {
+        Cow::Owned(Cow::into_owned(self))
+    }
+}
+
+impl<T> IntoOwned for Option<T>
+where
+    T: IntoOwned,
+{
+    type Owned = Option<T::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        self.map(T::into_owned)
+    }
+}
+
+impl<T> IntoOwned for Vec<T>
+where
+    T: IntoOwned,
+{
+    type Owned = Vec<T::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        self.into_iter().map(T::into_owned).collect()
+    }
+}
@@ -1,38 +1,239 @@
-use std::fmt::Debug;
+use std::{
+    borrow::Cow,
+    fmt::{Debug, Display, Formatter},
+    string::FromUtf8Error,
+};
 
 #[cfg(feature = "serdex")]
 use serde::{Deserialize, Serialize};
 
+use crate::types::{command::Mechanism, language::LanguageTag, IntoOwned, MessageNumber};
+
 // -- Greeting --
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Greeting {
-    /// An empty vector is used for "no code"
-    pub code: Vec<String>,
-    pub comment: String,
-    pub timestamp: Option<String>,
+pub struct Greeting<'a> {
+    pub code: Option<ResponseCode>,
+    pub comment: Cow<'a, str>,
+    pub timestamp: Option<Cow<'a, str>>,
+}
+
+impl<'a> Greeting<'a> {
+    /// Serializes the greeting.
+    ///
+    /// The timestamp (if any) is inserted at the position of the "<>" placeholder in the comment.
+    /// When the comment has no placeholder, the timestamp is appended (separated by a space).
+    /// Accordingly, [greeting](crate::parse::greeting) omits the placeholder when the timestamp
+    /// ends the greeting.
+    ///
+    /// Without a response code, a comment starting with "[" is escaped with a leading space, which
+    /// is removed by [greeting](crate::parse::greeting).
+    ///
+    /// # Panics
+    ///
+    /// Panics when the comment contains "<" (except for the placeholder in front of a timestamp),
+    /// because it would be parsed as (part of) a timestamp.
+    pub fn serialize(&self) -> Vec<u8> {
+        let rest = match self.timestamp {
+            Some(_) => self.comment.replacen("<>", "", 1),
+            None => self.comment.to_string(),
+        };
+        assert!(
+            !rest.contains('<'),
+            "greeting comment must not contain \"<\": {:?}",
+            self.comment
+        );
+
+        let mut out = b"+OK".to_vec();
+
+        if let Some(code) = self.code.as_ref() {
+            out.push(b' ');
+            serialize_code(code, &mut out);
+        } else if self.comment.trim_start_matches(' ').starts_with('[') {
+            out.push(b' ');
+        }
+
+        let comment = match self.timestamp.as_ref() {
+            Some(timestamp) => {
+                let timestamp = format!("<{}>", timestamp);
+
+                if self.comment.contains("<>") {
+                    self.comment.replacen("<>", &timestamp, 1)
+                } else if self.comment.is_empty() {
+                    timestamp
+                } else {
+                    format!("{} {}", self.comment, timestamp)
+                }
+            }
+            None => self.comment.to_string(),
+        };
+
+        if !comment.is_empty() {
+            out.push(b' ');
+            out.extend_from_slice(comment.as_bytes());
+        }
+
+        out.extend_from_slice(b"\r\n");
+        out
+    }
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SingleLine {
-    /// An empty vector is used for "no code"
-    pub code: Vec<String>,
-    pub comment: String,
+pub struct SingleLine<'a> {
+    pub code: Option<ResponseCode>,
+    pub comment: Cow<'a, str>,
+}
+
+/// Extended response code, e.g., "[SYS/TEMP]" (RFC 2449).
+///
+/// Response codes are hierarchical, i.e., consist of levels separated by "/". Unknown codes and
+/// codes, which are more specific than the known ones (e.g., "SYS/TEMP/DISK"), are kept as
+/// [Other](ResponseCode::Other). Use [is_within](ResponseCode::is_within) to match both.
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResponseCode {
+    // -- RFC2449 --
+    /// The maildrop is locked by another session ("IN-USE").
+    InUse,
+    /// The user logged in too recently ("LOGIN-DELAY").
+    LoginDelay,
+    // -- RFC3206 --
+    /// Temporary system failure ("SYS/TEMP").
+    SysTemp,
+    /// Permanent system failure ("SYS/PERM").
+    SysPerm,
+    /// The credentials were rejected ("AUTH").
+    Auth,
+    // -- RFC6856 --
+    /// The command requires UTF-8 mode ("UTF8").
+    Utf8,
+    // -----------------------
+    Other(Vec<String>),
+}
+
+impl ResponseCode {
+    /// Creates a response code from its levels, e.g., `["SYS", "TEMP"]`.
+    ///
+    /// Known codes are matched case-insensitively.
+    pub fn from_levels<S>(levels: &[S]) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let known = [
+            (&["IN-USE"][..], ResponseCode::InUse),
+            (&["LOGIN-DELAY"], ResponseCode::LoginDelay),
+            (&["SYS", "TEMP"], ResponseCode::SysTemp),
+            (&["SYS", "PERM"], ResponseCode::SysPerm),
+            (&["AUTH"], ResponseCode::Auth),
+            (&["UTF8"], ResponseCode::Utf8),
+        ];
+
+        for (known, code) in known {
+            if levels_eq(known, levels) {
+                return code;
+            }
+        }
+
+        ResponseCode::Other(levels.iter().map(|level| level.as_ref().into()).collect())
+    }
+
+    /// Returns the levels, e.g., `["SYS", "TEMP"]`.
+    pub fn levels(&self) -> Vec<&str> {
+        use ResponseCode::*;
+
+        match self {
+            InUse => vec!["IN-USE"],
+            LoginDelay => vec!["LOGIN-DELAY"],
+            SysTemp => vec!["SYS", "TEMP"],
+            SysPerm => vec!["SYS", "PERM"],
+            Auth => vec!["AUTH"],
+            Utf8 => vec!["UTF8"],
+            Other(levels) => levels.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Returns `true` when the response code is `code` or one of its sub-codes.
+    ///
+    /// E.g., "SYS/TEMP" and "SYS/TEMP/DISK" are both within "SYS/TEMP" and "SYS".
+    pub fn is_within(&self, code: &str) -> bool {
+        let prefix: Vec<&str> = code.split('/').collect();
+        let levels = self.levels();
+
+        levels.len() >= prefix.len() && levels_eq(&prefix, &levels[..prefix.len()])
+    }
+
+    /// Returns `true` when the failure is temporary, i.e., the command may succeed later without
+    /// any change ("IN-USE", "LOGIN-DELAY", and "SYS/TEMP").
+    pub fn is_temporary(&self) -> bool {
+        ["IN-USE", "LOGIN-DELAY", "SYS/TEMP"]
+            .iter()
+            .any(|code| self.is_within(code))
+    }
+
+    /// Returns `true` when the failure is permanent, i.e., repeating the command without any
+    /// change will fail again ("SYS/PERM", "AUTH", and "UTF8").
+    pub fn is_permanent(&self) -> bool {
+        ["SYS/PERM", "AUTH", "UTF8"]
+            .iter()
+            .any(|code| self.is_within(code))
+    }
+}
+
+fn levels_eq<A, B>(a: &[A], b: &[B]) -> bool
+where
+    A: AsRef<str>,
+    B: AsRef<str>,
+{
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.as_ref().eq_ignore_ascii_case(b.as_ref()))
+}
+
+impl Display for ResponseCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.levels().join("/"))
+    }
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MultiLine<T>
+pub struct MultiLine<'a, T>
 where
     // TODO: relax trait bound
     T: Debug + Clone + PartialEq + Eq,
 {
-    pub head: SingleLine,
+    pub head: SingleLine<'a>,
     pub body: Vec<T>,
 }
 
+/// Views on the body of a message, i.e., the response to RETR or TOP.
+impl<'a> MultiLine<'a, Cow<'a, [u8]>> {
+    /// Returns the (unstuffed) message as one contiguous buffer, i.e., every line followed by CRLF.
+    pub fn body_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.iter().map(|line| line.len() + 2).sum());
+
+        for line in self.body.iter() {
+            out.extend_from_slice(line);
+            out.extend_from_slice(b"\r\n");
+        }
+
+        out
+    }
+
+    /// Returns the message as a string or an error when the message is not valid UTF-8.
+    pub fn body_utf8(&self) -> Result<String, FromUtf8Error> {
+        String::from_utf8(self.body_bytes())
+    }
+
+    /// Returns the message as a string, replacing invalid UTF-8 sequences with U+FFFD.
+    pub fn body_utf8_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.body_bytes()).into_owned()
+    }
+}
+
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Response<O, E>
@@ -59,42 +260,288 @@ where
     }
 }
 
+impl<O, E> Response<O, E>
+where
+    // TODO: relax trait bound
+    O: Debug + Clone + PartialEq + Eq + SerializePayload,
+    E: Debug + Clone + PartialEq + Eq + SerializePayload,
+{
+    /// Serializes the response, i.e., the status indicator followed by the payload.
+    pub fn serialize(&self) -> Vec<u8> {
+        let (mut out, payload) = match self {
+            Response::Ok(o) => (b"+OK".to_vec(), o.serialize_payload()),
+            Response::Err(e) => (b"-ERR".to_vec(), e.serialize_payload()),
+        };
+
+        out.extend_from_slice(&payload);
+        out
+    }
+}
+
+/// Response of the server during the AUTH exchange (RFC 5034).
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthResponse<'a> {
+    /// continue-req = "+" SP [base64] CRLF
+    ///
+    /// The challenge is base64 encoded (and may be empty).
+    Continue(Cow<'a, str>),
+    /// The final "+OK" or "-ERR" response.
+    Done(Response<SingleLine<'a>, SingleLine<'a>>),
+}
+
+impl<'a> AuthResponse<'a> {
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            AuthResponse::Continue(challenge) => format!("+ {}\r\n", challenge).into_bytes(),
+            AuthResponse::Done(response) => response.serialize(),
+        }
+    }
+}
+
+/// Any response, i.e., the greeting or the typed response to any [Command](crate::types::Command).
+///
+/// See [parse::response](crate::parse::response).
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct DropListing {
+pub enum AnyResponse<'a> {
+    Greeting(Greeting<'a>),
+    User(Response<SingleLine<'a>, SingleLine<'a>>),
+    Pass(Response<SingleLine<'a>, SingleLine<'a>>),
+    Stat(Response<DropListing<'a>, SingleLine<'a>>),
+    ListAll(Response<MultiLine<'a, ScanListing<'a>>, SingleLine<'a>>),
+    List(Response<ScanListing<'a>, SingleLine<'a>>),
+    Retr(Response<MultiLine<'a, Cow<'a, [u8]>>, SingleLine<'a>>),
+    Dele(Response<SingleLine<'a>, SingleLine<'a>>),
+    Noop(Response<SingleLine<'a>, SingleLine<'a>>),
+    Rset(Response<SingleLine<'a>, SingleLine<'a>>),
+    Quit(Response<SingleLine<'a>, SingleLine<'a>>),
+    Apop(Response<SingleLine<'a>, SingleLine<'a>>),
+    Top(Response<MultiLine<'a, Cow<'a, [u8]>>, SingleLine<'a>>),
+    UidlAll(Response<MultiLine<'a, UniqueIdListing<'a>>, SingleLine<'a>>),
+    Uidl(Response<UniqueIdListing<'a>, SingleLine<'a>>),
+    Capa(Response<MultiLine<'a, Capability<'a>>, SingleLine<'a>>),
+    Stls(Response<SingleLine<'a>, SingleLine<'a>>),
+    AuthAll(Response<MultiLine<'a, Cow<'a, str>>, SingleLine<'a>>),
+    /// A challenge or the final response of the AUTH exchange.
+    Auth(AuthResponse<'a>),
+    Utf8(Response<SingleLine<'a>, SingleLine<'a>>),
+    LangAll(Response<MultiLine<'a, LanguageListing<'a>>, SingleLine<'a>>),
+    Lang(Response<SingleLine<'a>, SingleLine<'a>>),
+}
+
+impl<'a> AnyResponse<'a> {
+    pub fn serialize(&self) -> Vec<u8> {
+        use AnyResponse::*;
+
+        match self {
+            Greeting(greeting) => greeting.serialize(),
+            User(response) | Pass(response) | Dele(response) | Noop(response) | Rset(response)
+            | Quit(response) | Apop(response) | Stls(response) | Utf8(response)
+            | Lang(response) => response.serialize(),
+            Stat(response) => response.serialize(),
+            ListAll(response) => response.serialize(),
+            List(response) => response.serialize(),
+            Retr(response) | Top(response) => response.serialize(),
+            UidlAll(response) => response.serialize(),
+            Uidl(response) => response.serialize(),
+            Capa(response) => response.serialize(),
+            AuthAll(response) => response.serialize(),
+            Auth(response) => response.serialize(),
+            LangAll(response) => response.serialize(),
+        }
+    }
+}
+
+// -- Serialization --
+
+/// Serialization of everything following the status indicator ("+OK" or "-ERR") of a response.
+///
+/// This includes the leading SP (if required), the final CRLF, and, in case of multi-line
+/// responses, the body and the terminating "." line.
+pub trait SerializePayload {
+    fn serialize_payload(&self) -> Vec<u8>;
+}
+
+/// Serialization of a single line of a multi-line response body (without CRLF).
+pub trait SerializeLine {
+    fn serialize_line(&self) -> Vec<u8>;
+}
+
+fn serialize_code(code: &ResponseCode, out: &mut Vec<u8>) {
+    out.push(b'[');
+    out.extend_from_slice(code.to_string().as_bytes());
+    out.push(b']');
+}
+
+impl<'a> SerializePayload for SingleLine<'a> {
+    fn serialize_payload(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        if let Some(code) = self.code.as_ref() {
+            out.push(b' ');
+            serialize_code(code, &mut out);
+        } else if self.comment.contains('[') {
+            // Without a response code, the text must not contain "[" (RFC 2449). The comment is
+            // escaped with a leading space, which is removed by the parser.
+            out.push(b' ');
+        }
+
+        if !self.comment.is_empty() {
+            out.push(b' ');
+            out.extend_from_slice(self.comment.as_bytes());
+        }
+
+        out.extend_from_slice(b"\r\n");
+        out
+    }
+}
+
+impl<'a, T> SerializePayload for MultiLine<'a, T>
+where
+    T: Debug + Clone + PartialEq + Eq + SerializeLine,
+{
+    fn serialize_payload(&self) -> Vec<u8> {
+        let mut out = self.head.serialize_payload();
+
+        for line in self.body.iter() {
+            out.extend_from_slice(&line.serialize_line());
+            out.extend_from_slice(b"\r\n");
+        }
+
+        out.extend_from_slice(b".\r\n");
+        out
+    }
+}
+
+impl<'a> SerializePayload for DropListing<'a> {
+    fn serialize_payload(&self) -> Vec<u8> {
+        let mut out = format!(" {} {}", self.message_count, self.maildrop_size).into_bytes();
+        serialize_trailing(&mut out, &self.trailing);
+        out.extend_from_slice(b"\r\n");
+        out
+    }
+}
+
+impl<'a> SerializePayload for ScanListing<'a> {
+    fn serialize_payload(&self) -> Vec<u8> {
+        let mut out = vec![b' '];
+        out.extend_from_slice(&self.serialize_line());
+        out.extend_from_slice(b"\r\n");
+        out
+    }
+}
+
+impl<'a> SerializeLine for ScanListing<'a> {
+    fn serialize_line(&self) -> Vec<u8> {
+        let mut out = format!("{} {}", self.message_id, self.message_size).into_bytes();
+        serialize_trailing(&mut out, &self.trailing);
+        out
+    }
+}
+
+impl<'a> SerializePayload for UniqueIdListing<'a> {
+    fn serialize_payload(&self) -> Vec<u8> {
+        let mut out = vec![b' '];
+        out.extend_from_slice(&self.serialize_line());
+        out.extend_from_slice(b"\r\n");
+        out
+    }
+}
+
+impl<'a> SerializeLine for UniqueIdListing<'a> {
+    fn serialize_line(&self) -> Vec<u8> {
+        let mut out = format!("{} {}", self.message_id, self.message_uid).into_bytes();
+        serialize_trailing(&mut out, &self.trailing);
+        out
+    }
+}
+
+fn serialize_trailing(out: &mut Vec<u8>, trailing: &Option<Cow<'_, str>>) {
+    if let Some(trailing) = trailing {
+        out.push(b' ');
+        out.extend_from_slice(trailing.as_bytes());
+    }
+}
+
+impl<'a> SerializeLine for LanguageListing<'a> {
+    fn serialize_line(&self) -> Vec<u8> {
+        format!("{} {}", self.tag, self.description).into_bytes()
+    }
+}
+
+impl<'a> SerializeLine for Capability<'a> {
+    fn serialize_line(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+/// Lines of a message are byte-stuffed, i.e., lines starting with "." are prefixed with an
+/// additional "." (see RFC 1939, section 3).
+impl<'a> SerializeLine for Cow<'a, [u8]> {
+    fn serialize_line(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len() + 1);
+
+        if self.starts_with(b".") {
+            out.push(b'.');
+        }
+
+        out.extend_from_slice(self);
+        out
+    }
+}
+
+/// See the implementation for `Cow<[u8]>`.
+impl<'a> SerializeLine for Cow<'a, str> {
+    fn serialize_line(&self) -> Vec<u8> {
+        Cow::Borrowed(self.as_bytes()).serialize_line()
+    }
+}
+
+#[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropListing<'a> {
     pub message_count: u32,
-    pub maildrop_size: u32,
+    /// Size in octets.
+    pub maildrop_size: u64,
+    /// Text following the listing, e.g., "messages" in "+OK 2 320 messages".
+    pub trailing: Option<Cow<'a, str>>,
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ScanListing {
-    pub message_id: u32,
-    pub message_size: u32,
+pub struct ScanListing<'a> {
+    pub message_id: MessageNumber,
+    /// Size in octets.
+    pub message_size: u64,
+    /// Additional information about the message, which servers may append (RFC 1939).
+    pub trailing: Option<Cow<'a, str>>,
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct UniqueIdListing {
-    pub message_id: u32,
-    pub message_uid: String,
+pub struct UniqueIdListing<'a> {
+    pub message_id: MessageNumber,
+    pub message_uid: Cow<'a, str>,
+    /// Text following the unique-id.
+    pub trailing: Option<Cow<'a, str>>,
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct LanguageListing {
-    pub tag: String, // TODO: see RFC5646
-    pub description: String,
+pub struct LanguageListing<'a> {
+    pub tag: LanguageTag,
+    pub description: Cow<'a, str>,
 }
 
 #[cfg_attr(feature = "serdex", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Capability {
+pub enum Capability<'a> {
     // -- RFC2449 --
     Top,
     User,
     Sasl {
-        mechanisms: Vec<String>, // TODO: String --> Mechanism
+        mechanisms: Vec<Mechanism>,
     },
     RespCodes,
     LoginDelay {
@@ -108,7 +555,7 @@ pub enum Capability {
     },
     Uidl,
     Implementation {
-        text: String,
+        text: Cow<'a, str>,
     },
     // -- RFC2595 --
     Stls,
@@ -121,53 +568,63 @@ pub enum Capability {
     Lang,
     // -----------------------
     Other {
-        tag: String,
-        parameters: Vec<String>,
+        tag: Cow<'a, str>,
+        parameters: Vec<Cow<'a, str>>,
     },
 }
 
-impl ToString for Capability {
-    fn to_string(&self) -> String {
+impl<'a> Display for Capability<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         use Capability::*;
 
         match self {
-            Top => "TOP".into(),
-            User => "USER".into(),
+            Top => write!(f, "TOP"),
+            User => write!(f, "USER"),
             Sasl { mechanisms } => {
-                format!("SASL {}", mechanisms.join(" "))
+                write!(f, "SASL")?;
+                for mechanism in mechanisms {
+                    write!(f, " {}", mechanism)?;
+                }
+                Ok(())
             }
-            RespCodes => "RESP-CODES".into(),
+            RespCodes => write!(f, "RESP-CODES"),
             LoginDelay {
                 minimum_seconds,
                 per_user,
             } => {
-                let mut out = format!("LOGIN-DELAY {}", minimum_seconds);
+                write!(f, "LOGIN-DELAY {}", minimum_seconds)?;
                 if *per_user {
-                    out.push_str(" USER");
+                    write!(f, " USER")?;
                 }
-                out
+                Ok(())
             }
-            Pipelining => "PIPELINING".into(),
+            Pipelining => write!(f, "PIPELINING"),
             Expire { policy, per_user } => {
-                let mut out = format!("EXPIRE {}", policy.to_string());
+                write!(f, "EXPIRE {}", policy)?;
                 if *per_user {
-                    out.push_str(" USER");
+                    write!(f, " USER")?;
                 }
-                out
+                Ok(())
             }
-            Uidl => "UIDL".into(),
-            Implementation { text: tag } => format!("IMPLEMENTATION {}", tag),
-            Stls => "STLS".into(),
-            AuthRespCode => "AUTH-RESP-CODE".into(),
+            Uidl => write!(f, "UIDL"),
+            Implementation { text: tag } => write!(f, "IMPLEMENTATION {}", tag),
+            Stls => write!(f, "STLS"),
+            AuthRespCode => write!(f, "AUTH-RESP-CODE"),
             Utf8 { in_credentials } => {
                 if *in_credentials {
-                    "UTF8 USER".into()
+                    write!(f, "UTF8 USER")
                 } else {
-                    "UTF8".into()
+                    write!(f, "UTF8")
+                }
+            }
+            Lang => write!(f, "LANG"),
+            Other { tag, parameters } => {
+                write!(f, "{}", tag)?;
+                for parameter in parameters {
+                    write!(f, " {}", parameter)?;
                 }
+                Ok(())
             }
-            Lang => "LANG".into(),
-            Other { tag, parameters } => format!("{} {}", tag, parameters.join(" ")),
         }
     }
 }
@@ -179,11 +636,584 @@ pub enum ExpirePolicy {
     MinimumDays(u32),
 }
 
-impl ToString for ExpirePolicy {
-    fn to_string(&self) -> String {
+impl Display for ExpirePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpirePolicy::Never => write!(f, "NEVER"),
+            ExpirePolicy::MinimumDays(days) => write!(f, "{}", days),
+        }
+    }
+}
+
+// -- Ownership --
+
+impl<'a> IntoOwned for Greeting<'a> {
+    type Owned = Greeting<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        Greeting {
+            code: self.code,
+            comment: Cow::Owned(self.comment.into_owned()),
+            timestamp: self.timestamp.into_owned(),
+        }
+    }
+}
+
+impl<'a> IntoOwned for SingleLine<'a> {
+    type Owned = SingleLine<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        SingleLine {
+            code: self.code,
+            comment: Cow::Owned(self.comment.into_owned()),
+        }
+    }
+}
+
+impl<'a, T> IntoOwned for MultiLine<'a, T>
+where
+    T: Debug + Clone + PartialEq + Eq + IntoOwned,
+    T::Owned: Debug + Clone + PartialEq + Eq,
+{
+    type Owned = MultiLine<'static, T::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        MultiLine {
+            head: self.head.into_owned(),
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+impl<O, E> IntoOwned for Response<O, E>
+where
+    O: Debug + Clone + PartialEq + Eq + IntoOwned,
+    E: Debug + Clone + PartialEq + Eq + IntoOwned,
+    O::Owned: Debug + Clone + PartialEq + Eq,
+    E::Owned: Debug + Clone + PartialEq + Eq,
+{
+    type Owned = Response<O::Owned, E::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        match self {
+            Response::Ok(o) => Response::Ok(o.into_owned()),
+            Response::Err(e) => Response::Err(e.into_owned()),
+        }
+    }
+}
+
+impl<'a> IntoOwned for AuthResponse<'a> {
+    type Owned = AuthResponse<'static>;
+
+    fn into_owned(self) -> Self::Owned {
         match self {
-            ExpirePolicy::Never => "NEVER".into(),
-            ExpirePolicy::MinimumDays(days) => days.to_string(),
+            AuthResponse::Continue(challenge) => {
+                AuthResponse::Continue(Cow::Owned(challenge.into_owned()))
+            }
+            AuthResponse::Done(response) => AuthResponse::Done(response.into_owned()),
+        }
+    }
+}
+
+impl<'a> IntoOwned for AnyResponse<'a> {
+    type Owned = AnyResponse<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        use AnyResponse::*;
+
+        match self {
+            Greeting(greeting) => Greeting(greeting.into_owned()),
+            User(response) => User(response.into_owned()),
+            Pass(response) => Pass(response.into_owned()),
+            Stat(response) => Stat(response.into_owned()),
+            ListAll(response) => ListAll(response.into_owned()),
+            List(response) => List(response.into_owned()),
+            Retr(response) => Retr(response.into_owned()),
+            Dele(response) => Dele(response.into_owned()),
+            Noop(response) => Noop(response.into_owned()),
+            Rset(response) => Rset(response.into_owned()),
+            Quit(response) => Quit(response.into_owned()),
+            Apop(response) => Apop(response.into_owned()),
+            Top(response) => Top(response.into_owned()),
+            UidlAll(response) => UidlAll(response.into_owned()),
+            Uidl(response) => Uidl(response.into_owned()),
+            Capa(response) => Capa(response.into_owned()),
+            Stls(response) => Stls(response.into_owned()),
+            AuthAll(response) => AuthAll(response.into_owned()),
+            Auth(response) => Auth(response.into_owned()),
+            Utf8(response) => Utf8(response.into_owned()),
+            LangAll(response) => LangAll(response.into_owned()),
+            Lang(response) => Lang(response.into_owned()),
         }
     }
 }
+
+impl<'a> IntoOwned for DropListing<'a> {
+    type Owned = DropListing<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        DropListing {
+            message_count: self.message_count,
+            maildrop_size: self.maildrop_size,
+            trailing: self.trailing.into_owned(),
+        }
+    }
+}
+
+impl<'a> IntoOwned for ScanListing<'a> {
+    type Owned = ScanListing<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        ScanListing {
+            message_id: self.message_id,
+            message_size: self.message_size,
+            trailing: self.trailing.into_owned(),
+        }
+    }
+}
+
+impl<'a> IntoOwned for UniqueIdListing<'a> {
+    type Owned = UniqueIdListing<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        UniqueIdListing {
+            message_id: self.message_id,
+            message_uid: Cow::Owned(self.message_uid.into_owned()),
+            trailing: self.trailing.into_owned(),
+        }
+    }
+}
+
+impl<'a> IntoOwned for LanguageListing<'a> {
+    type Owned = LanguageListing<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        LanguageListing {
+            tag: self.tag,
+            description: Cow::Owned(self.description.into_owned()),
+        }
+    }
+}
+
+impl<'a> IntoOwned for Capability<'a> {
+    type Owned = Capability<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        use Capability::*;
+
+        match self {
+            Top => Top,
+            User => User,
+            Sasl { mechanisms } => Sasl { mechanisms },
+            RespCodes => RespCodes,
+            LoginDelay {
+                minimum_seconds,
+                per_user,
+            } => LoginDelay {
+                minimum_seconds,
+                per_user,
+            },
+            Pipelining => Pipelining,
+            Expire { policy, per_user } => Expire { policy, per_user },
+            Uidl => Uidl,
+            Implementation { text } => Implementation {
+                text: Cow::Owned(text.into_owned()),
+            },
+            Stls => Stls,
+            AuthRespCode => AuthRespCode,
+            Utf8 { in_credentials } => Utf8 { in_credentials },
+            Lang => Lang,
+            Other { tag, parameters } => Other {
+                tag: Cow::Owned(tag.into_owned()),
+                parameters: parameters.into_owned(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::{
+        greeting, response_capa, response_lang_all, response_list, response_list_all,
+        response_retr, response_stat, response_uidl, response_uidl_all, response_user,
+    };
+
+    #[test]
+    fn test_serialize_greeting() {
+        let tests: &[(Greeting, &[u8])] = &[
+            (
+                Greeting {
+                    code: None,
+                    comment: "".into(),
+                    timestamp: None,
+                },
+                b"+OK\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: "Hello World!".into(),
+                    timestamp: None,
+                },
+                b"+OK Hello World!\r\n",
+            ),
+            (
+                Greeting {
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
+                    comment: "Hello <> World!".into(),
+                    timestamp: Some("123@example.org".into()),
+                },
+                b"+OK [a] Hello <123@example.org> World!\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: "".into(),
+                    timestamp: Some("123".into()),
+                },
+                b"+OK <123>\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: "POP3 server ready".into(),
+                    timestamp: Some("1896.697170952@dbc.mtview.ca.us".into()),
+                },
+                b"+OK POP3 server ready <1896.697170952@dbc.mtview.ca.us>\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: "Hello<>".into(),
+                    timestamp: Some("123".into()),
+                },
+                b"+OK Hello<123>\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: "[a] Hello World!".into(),
+                    timestamp: None,
+                },
+                b"+OK  [a] Hello World!\r\n",
+            ),
+            (
+                Greeting {
+                    code: None,
+                    comment: " [a]".into(),
+                    timestamp: Some("123".into()),
+                },
+                b"+OK   [a] <123>\r\n",
+            ),
+            (
+                Greeting {
+                    code: Some(ResponseCode::Other(vec!["a".into()])),
+                    comment: "[b]".into(),
+                    timestamp: None,
+                },
+                b"+OK [a] [b]\r\n",
+            ),
+        ];
+
+        for (test, expected) in tests {
+            let serialized = test.serialize();
+            assert_eq!(serialized, *expected);
+
+            let (rem, got) = greeting(&serialized).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(*test, got);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_serialize_greeting_timestamp_in_comment() {
+        Greeting {
+            code: None,
+            comment: "Hello <123> World!".into(),
+            timestamp: None,
+        }
+        .serialize();
+    }
+
+    #[test]
+    fn test_serialize_single_line() {
+        let tests: &[(Response<SingleLine, SingleLine>, &[u8])] = &[
+            (
+                Response::Ok(SingleLine {
+                    code: None,
+                    comment: "".into(),
+                }),
+                b"+OK\r\n",
+            ),
+            (
+                Response::Ok(SingleLine {
+                    code: None,
+                    comment: "mrose is a real hoopy frood".into(),
+                }),
+                b"+OK mrose is a real hoopy frood\r\n",
+            ),
+            (
+                Response::Err(SingleLine {
+                    code: Some(ResponseCode::InUse),
+                    comment: "maildrop already locked".into(),
+                }),
+                b"-ERR [IN-USE] maildrop already locked\r\n",
+            ),
+            (
+                Response::Err(SingleLine {
+                    code: Some(ResponseCode::SysTemp),
+                    comment: "".into(),
+                }),
+                b"-ERR [SYS/TEMP]\r\n",
+            ),
+            (
+                Response::Ok(SingleLine {
+                    code: None,
+                    comment: "[a] is not a response code".into(),
+                }),
+                b"+OK  [a] is not a response code\r\n",
+            ),
+            (
+                Response::Err(SingleLine {
+                    code: None,
+                    comment: " see [1]".into(),
+                }),
+                b"-ERR   see [1]\r\n",
+            ),
+        ];
+
+        for (test, expected) in tests {
+            let serialized = test.serialize();
+            assert_eq!(serialized, *expected);
+
+            let (rem, got) = response_user(&serialized).unwrap();
+            assert!(rem.is_empty());
+            assert_eq!(*test, got);
+        }
+    }
+
+    #[test]
+    fn test_serialize_listings() {
+        let stat: Response<DropListing, SingleLine> = Response::Ok(DropListing {
+            message_count: 2,
+            maildrop_size: 320,
+            trailing: None,
+        });
+        assert_eq!(stat.serialize(), b"+OK 2 320\r\n");
+        assert_eq!(response_stat(&stat.serialize()).unwrap().1, stat);
+
+        let list: Response<ScanListing, SingleLine> = Response::Ok(ScanListing {
+            message_id: MessageNumber::new(2).unwrap(),
+            message_size: 200,
+            trailing: None,
+        });
+        assert_eq!(list.serialize(), b"+OK 2 200\r\n");
+        assert_eq!(response_list(&list.serialize()).unwrap().1, list);
+
+        let list_err: Response<ScanListing, SingleLine> = Response::Err(SingleLine {
+            code: None,
+            comment: "no such message".into(),
+        });
+        assert_eq!(list_err.serialize(), b"-ERR no such message\r\n");
+        assert_eq!(response_list(&list_err.serialize()).unwrap().1, list_err);
+
+        let uidl: Response<UniqueIdListing, SingleLine> = Response::Ok(UniqueIdListing {
+            message_id: MessageNumber::new(2).unwrap(),
+            message_uid: "QhdPYR:00WBw1Ph7x7".into(),
+            trailing: None,
+        });
+        assert_eq!(uidl.serialize(), b"+OK 2 QhdPYR:00WBw1Ph7x7\r\n");
+        assert_eq!(response_uidl(&uidl.serialize()).unwrap().1, uidl);
+
+        let stat: Response<DropListing, SingleLine> = Response::Ok(DropListing {
+            message_count: 2,
+            maildrop_size: 320,
+            trailing: Some("messages".into()),
+        });
+        assert_eq!(stat.serialize(), b"+OK 2 320 messages\r\n");
+        assert_eq!(response_stat(&stat.serialize()).unwrap().1, stat);
+    }
+
+    #[test]
+    fn test_serialize_multi_line() {
+        let list_all = Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "2 messages (320 octets)".into(),
+            },
+            body: vec![
+                ScanListing {
+                    message_id: MessageNumber::new(1).unwrap(),
+                    message_size: 120,
+                    trailing: None,
+                },
+                ScanListing {
+                    message_id: MessageNumber::new(2).unwrap(),
+                    message_size: 200,
+                    trailing: None,
+                },
+            ],
+        });
+        let serialized = list_all.serialize();
+        assert_eq!(
+            serialized,
+            b"+OK 2 messages (320 octets)\r\n1 120\r\n2 200\r\n.\r\n"
+        );
+        assert_eq!(response_list_all(&serialized).unwrap().1, list_all);
+
+        let uidl_all = Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "".into(),
+            },
+            body: vec![UniqueIdListing {
+                message_id: MessageNumber::new(1).unwrap(),
+                message_uid: "whqtswO00WBw418f9t5JxYwZ".into(),
+                trailing: None,
+            }],
+        });
+        let serialized = uidl_all.serialize();
+        assert_eq!(serialized, b"+OK\r\n1 whqtswO00WBw418f9t5JxYwZ\r\n.\r\n");
+        assert_eq!(response_uidl_all(&serialized).unwrap().1, uidl_all);
+
+        let lang_all = Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "Language listing follows:".into(),
+            },
+            body: vec![
+                LanguageListing {
+                    tag: "en".parse().unwrap(),
+                    description: "English".into(),
+                },
+                LanguageListing {
+                    tag: "i-default".parse().unwrap(),
+                    description: "Default language".into(),
+                },
+            ],
+        });
+        let serialized = lang_all.serialize();
+        assert_eq!(response_lang_all(&serialized).unwrap().1, lang_all);
+
+        let capa = Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "Capability list follows".into(),
+            },
+            body: vec![
+                Capability::Top,
+                Capability::Sasl {
+                    mechanisms: vec![Mechanism::Plain],
+                },
+                Capability::Expire {
+                    policy: ExpirePolicy::Never,
+                    per_user: false,
+                },
+                Capability::Other {
+                    tag: "X-EMPTY".into(),
+                    parameters: vec![],
+                },
+            ],
+        });
+        let serialized = capa.serialize();
+        assert_eq!(
+            serialized,
+            b"+OK Capability list follows\r\nTOP\r\nSASL PLAIN\r\nEXPIRE NEVER\r\nX-EMPTY\r\n.\r\n"
+        );
+        assert_eq!(response_capa(&serialized).unwrap().1, capa);
+
+        let retr: Response<MultiLine<Cow<[u8]>>, SingleLine> = Response::Ok(MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "120 octets".into(),
+            },
+            body: vec![
+                Cow::Borrowed(&b"Subject: Test"[..]),
+                Cow::Borrowed(&b""[..]),
+                Cow::Borrowed(&b"."[..]),
+                Cow::Borrowed(&b".."[..]),
+                Cow::Borrowed(&b".foo"[..]),
+                Cow::Borrowed(&b"foo."[..]),
+                Cow::Borrowed(&b"Gr\xfc\xdfe"[..]),
+            ],
+        });
+        let serialized = retr.serialize();
+        assert_eq!(
+            serialized,
+            b"+OK 120 octets\r\nSubject: Test\r\n\r\n..\r\n...\r\n..foo\r\nfoo.\r\nGr\xfc\xdfe\r\n.\r\n"
+        );
+        assert_eq!(response_retr(&serialized).unwrap().1, retr);
+
+        let retr: Response<MultiLine<Cow<[u8]>>, SingleLine> = Response::Err(SingleLine {
+            code: None,
+            comment: "no such message".into(),
+        });
+        let serialized = retr.serialize();
+        assert_eq!(serialized, b"-ERR no such message\r\n");
+        assert_eq!(response_retr(&serialized).unwrap().1, retr);
+    }
+
+    #[test]
+    fn test_body_views() {
+        let mut message = MultiLine {
+            head: SingleLine {
+                code: None,
+                comment: "".into(),
+            },
+            body: vec![
+                Cow::Borrowed(&b"Subject: Test"[..]),
+                Cow::Borrowed(&b""[..]),
+                Cow::Borrowed(&b"."[..]),
+            ],
+        };
+
+        assert_eq!(message.body_bytes(), b"Subject: Test\r\n\r\n.\r\n");
+        assert_eq!(message.body_utf8().unwrap(), "Subject: Test\r\n\r\n.\r\n");
+
+        message.body.push(Cow::Borrowed(&b"Gr\xfc\xdfe"[..]));
+        assert!(message.body_utf8().is_err());
+        assert_eq!(
+            message.body_utf8_lossy(),
+            "Subject: Test\r\n\r\n.\r\nGr\u{FFFD}\u{FFFD}e\r\n"
+        );
+    }
+
+    #[test]
+    fn test_response_code() {
+        assert_eq!(
+            ResponseCode::from_levels(&["sys", "temp"]),
+            ResponseCode::SysTemp
+        );
+        assert_eq!(ResponseCode::from_levels(&["IN-USE"]), ResponseCode::InUse);
+
+        let disk = ResponseCode::from_levels(&["SYS", "TEMP", "DISK"]);
+        assert_eq!(
+            disk,
+            ResponseCode::Other(vec!["SYS".into(), "TEMP".into(), "DISK".into()])
+        );
+        assert_eq!(disk.to_string(), "SYS/TEMP/DISK");
+        assert!(disk.is_within("SYS/TEMP"));
+        assert!(disk.is_within("sys"));
+        assert!(!disk.is_within("SYS/PERM"));
+        assert!(!ResponseCode::SysTemp.is_within("SYS/TEMP/DISK"));
+
+        assert!(disk.is_temporary());
+        assert!(ResponseCode::LoginDelay.is_temporary());
+        assert!(ResponseCode::Auth.is_permanent());
+        assert!(!ResponseCode::Auth.is_temporary());
+
+        let other = ResponseCode::from_levels(&["X-FOO"]);
+        assert!(!other.is_temporary() && !other.is_permanent());
+
+        let error = SingleLine {
+            code: Some(ResponseCode::SysPerm),
+            comment: "gone".into(),
+        };
+        assert_eq!(
+            Response::<SingleLine, _>::Err(error).serialize(),
+            b"-ERR [SYS/PERM] gone\r\n"
+        );
+    }
+}